@@ -0,0 +1,222 @@
+use std::collections::{HashSet, VecDeque};
+use crate::game::{Chain, Coordinates, Game};
+use crate::stones::{Stone, EMPTY};
+
+// Pass-alive stones of one player, and the points they own unconditionally
+#[derive(Clone, Debug, Default)]
+pub struct UnconditionalLife {
+    pub alive_stones: Vec<Coordinates>,
+    // empty points and opponent stones inside regions enclosed by pass-alive chains
+    pub territory: Vec<Coordinates>,
+}
+
+// Maximal connected set of points without a stone of the analysed color
+struct EnclosedRegion {
+    points: Vec<Coordinates>,
+    empty_points: Vec<Coordinates>,
+    bordering_chains: Vec<usize>,
+}
+
+fn enclosed_regions(game: &Game, color: Stone, chain_index: &[Vec<Option<usize>>]) -> Vec<EnclosedRegion> {
+    let (height, width) = game.shape();
+    let mut visited = vec![vec![false; width]; height];
+    let mut regions: Vec<EnclosedRegion> = vec![];
+    for x in 0..height {
+        for y in 0..width {
+            if visited[x][y] || *game.get(x, y) == color {
+                continue;
+            }
+            let mut region = EnclosedRegion { points: vec![], empty_points: vec![], bordering_chains: vec![] };
+            let mut queue: VecDeque<Coordinates> = VecDeque::from([(x, y)]);
+            visited[x][y] = true;
+            while let Some(current) = queue.pop_front() {
+                region.points.push(current);
+                if *game.get(current.0, current.1) == EMPTY {
+                    region.empty_points.push(current);
+                }
+                for (i, j) in game.get_board().neighbors(current) {
+                    if *game.get(i, j) == color {
+                        let chain = chain_index[i][j].unwrap();
+                        if !region.bordering_chains.contains(&chain) {
+                            region.bordering_chains.push(chain);
+                        }
+                    } else if !visited[i][j] {
+                        visited[i][j] = true;
+                        queue.push_back((i, j));
+                    }
+                }
+            }
+            regions.push(region);
+        }
+    }
+    regions
+}
+
+/*
+    Benson's algorithm for unconditional life.
+
+    A region enclosed by a player is vital to one of its chains if all the empty points of the region
+    are liberties of that chain. Chains with fewer than two vital regions are discarded, along with the
+    regions they border, until nothing changes. The chains left cannot be captured even if their owner
+    passes every turn.
+*/
+pub fn unconditional_life(game: &Game, color: Stone) -> UnconditionalLife {
+    let (height, width) = game.shape();
+    let chains: Vec<Chain> = game.chains().into_iter().filter(|chain| chain.color == color).collect();
+    let mut chain_index: Vec<Vec<Option<usize>>> = vec![vec![None; width]; height];
+    for (i, chain) in chains.iter().enumerate() {
+        for (x, y) in &chain.stones {
+            chain_index[*x][*y] = Some(i);
+        }
+    }
+    let liberties: Vec<HashSet<Coordinates>> = chains
+        .iter()
+        .map(|chain| chain.liberties.iter().copied().collect())
+        .collect();
+    let regions = enclosed_regions(game, color, &chain_index);
+    let vital_chains: Vec<Vec<usize>> = regions
+        .iter()
+        .map(|region| {
+            region
+                .bordering_chains
+                .iter()
+                .copied()
+                .filter(|chain| region.empty_points.iter().all(|point| liberties[*chain].contains(point)))
+                .collect()
+        })
+        .collect();
+
+    let mut alive_chains = vec![true; chains.len()];
+    let mut alive_regions = vec![true; regions.len()];
+    loop {
+        let mut changed = false;
+        for (chain, alive_chain) in alive_chains.iter_mut().enumerate() {
+            if !*alive_chain {
+                continue;
+            }
+            let n_vital_regions = (0..regions.len())
+                .filter(|region| alive_regions[*region] && vital_chains[*region].contains(&chain))
+                .count();
+            if n_vital_regions < 2 {
+                *alive_chain = false;
+                changed = true;
+            }
+        }
+        for (region_index, region) in regions.iter().enumerate() {
+            if alive_regions[region_index] && region.bordering_chains.iter().any(|chain| !alive_chains[*chain]) {
+                alive_regions[region_index] = false;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut unconditional_life = UnconditionalLife::default();
+    for (chain, alive) in chains.iter().zip(alive_chains.iter()) {
+        if *alive {
+            unconditional_life.alive_stones.extend(chain.stones.iter().copied());
+        }
+    }
+    // the opponent cannot live in a region whose empty points are all liberties of pass-alive chains
+    for (region, alive) in regions.iter().zip(alive_regions.iter()) {
+        let is_small = region
+            .empty_points
+            .iter()
+            .all(|point| region.bordering_chains.iter().any(|chain| liberties[*chain].contains(point)));
+        if *alive && !region.bordering_chains.is_empty() && is_small {
+            unconditional_life.territory.extend(region.points.iter().copied());
+        }
+    }
+    unconditional_life
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+    use crate::stones::{BLACK_STONE, WHITE_STONE};
+    use crate::test_support::position;
+
+    #[test]
+    fn chain_with_two_eyes_is_alive() {
+        let game = position(
+            &[
+                ".X.X.",
+                "XXXXX",
+                ".....",
+                "..O..",
+                ".....",
+            ],
+            Rules::default(),
+        );
+        let life = unconditional_life(&game, BLACK_STONE);
+        assert_eq!(life.alive_stones.len(), 7);
+        for eye in [(0, 0), (0, 2), (0, 4)] {
+            assert!(life.territory.contains(&eye));
+        }
+        assert!(!life.territory.contains(&(3, 2)));
+        assert!(unconditional_life(&game, WHITE_STONE).alive_stones.is_empty());
+    }
+
+    #[test]
+    fn false_eye_does_not_make_life() {
+        // the white stone cuts the chains around the eye at (0, 2), each of them only has one eye of its own
+        let false_eye = position(
+            &[
+                ".X.XX",
+                "XXXOX",
+                "....X",
+                ".....",
+                ".....",
+            ],
+            Rules::default(),
+        );
+        let life = unconditional_life(&false_eye, BLACK_STONE);
+        assert!(life.alive_stones.is_empty());
+        assert!(life.territory.is_empty());
+
+        let real_eye = position(
+            &[
+                ".X.XX",
+                "XXXXX",
+                "....X",
+                ".....",
+                ".....",
+            ],
+            Rules::default(),
+        );
+        assert_eq!(unconditional_life(&real_eye, BLACK_STONE).alive_stones.len(), 9);
+    }
+
+    #[test]
+    fn region_with_points_away_from_the_chain_is_not_vital() {
+        // the centre of the corner region is not a liberty, so the corner does not count as an eye
+        let one_eye = position(
+            &[
+                "...X.",
+                "...XX",
+                "...XX",
+                "XXXXX",
+            ],
+            Rules::default(),
+        );
+        assert!(unconditional_life(&one_eye, BLACK_STONE).alive_stones.is_empty());
+
+        // with two real eyes the chain lives, but the opponent could still live in the corner
+        let two_eyes = position(
+            &[
+                "...X.",
+                "...XX",
+                "...X.",
+                "XXXXX",
+            ],
+            Rules::default(),
+        );
+        let life = unconditional_life(&two_eyes, BLACK_STONE);
+        assert_eq!(life.alive_stones.len(), 9);
+        assert!(life.territory.contains(&(0, 4)) && life.territory.contains(&(2, 4)));
+        assert!(!life.territory.contains(&(1, 1)));
+    }
+}
@@ -0,0 +1,7 @@
+use std::io::{self, BufReader};
+use gobot_rs::gtp::GtpEngine;
+
+fn main() -> io::Result<()> {
+    let mut engine = GtpEngine::new();
+    engine.run(BufReader::new(io::stdin()), io::stdout().lock())
+}
@@ -1,26 +1,69 @@
-use crate::stones::{Stone};
-use std::ops::{Index, IndexMut};
+use crate::stones::{Stone, EMPTY};
+use crate::zobrist;
+use std::hash::{Hash, Hasher};
+use std::ops::Index;
 
 pub type BoardSize = (usize, usize);
-pub const SIDE: usize = 19;
-pub type BoardArray = [[Stone; SIDE]; SIDE];
+pub const DEFAULT_SIDE: usize = 19;
+pub const MIN_SIDE: usize = 2;
+pub const MAX_SIDE: usize = 25;
+pub type BoardArray = Vec<Vec<Stone>>;
 
-#[derive(Eq, Hash, PartialEq, Clone)]
+#[derive(Eq, PartialEq, Clone)]
 pub struct Board {
-    pub data:BoardArray,
-    pub board_side_length: usize,
-    pub shape:BoardSize
+    // private so that every change goes through `set` and keeps the hash up to date
+    data:BoardArray,
+    pub shape:BoardSize,
+    // Zobrist hash of the stones on the board, updated on every change made through `set`
+    hash: u64
 }
 
 impl Board {
-    pub fn new() -> Board {
-        let data: [[Stone;SIDE];SIDE] = [[None; SIDE]; SIDE];
+    pub fn new((height, width): BoardSize) -> Board {
+        if !Self::is_valid_shape((height, width)) {
+            panic!("Board sides must be between {} and {}", MIN_SIDE, MAX_SIDE)
+        }
+        let data: BoardArray = vec![vec![EMPTY; width]; height];
         Board {
             data,
-            board_side_length: SIDE,
-            shape: (SIDE, SIDE)
+            shape: (height, width),
+            hash: 0
         }
     }
+
+    pub fn square(side: usize) -> Board {
+        Self::new((side, side))
+    }
+
+    pub fn is_valid_shape((height, width): BoardSize) -> bool {
+        (MIN_SIDE..=MAX_SIDE).contains(&height) && (MIN_SIDE..=MAX_SIDE).contains(&width)
+    }
+
+    pub fn position_hash(&self) -> u64 {
+        self.hash
+    }
+
+    // Rows of the board from the top, read only
+    pub fn rows(&self) -> &BoardArray {
+        &self.data
+    }
+
+    pub fn set(&mut self, (x, y): (usize, usize), stone: Stone) {
+        let previous_stone = self.data[x][y];
+        self.hash ^= zobrist::stone_key((x, y), previous_stone) ^ zobrist::stone_key((x, y), stone);
+        self.data[x][y] = stone;
+    }
+
+    pub fn is_on_board(&self, (x, y): (usize, usize)) -> bool {
+        x < self.shape.0 && y < self.shape.1
+    }
+
+    pub fn neighbors(&self, (x, y): (usize, usize)) -> impl Iterator<Item = (usize, usize)> {
+        let (height, width) = self.shape;
+        [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)]
+            .into_iter()
+            .filter(move |&(i, j)| i < height && j < width)
+    }
 }
 
 impl Index<(usize, usize)> for Board
@@ -31,14 +74,15 @@ impl Index<(usize, usize)> for Board
     }
 }
 
-impl IndexMut<(usize, usize)> for Board{
-    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Self::Output {
-        &mut self.data[x][y]
+impl Hash for Board {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.shape.hash(state);
+        self.hash.hash(state);
     }
 }
 
 impl Default for Board {
     fn default() -> Self {
-        Self::new()
+        Self::square(DEFAULT_SIDE)
     }
 }
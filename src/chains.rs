@@ -0,0 +1,449 @@
+use crate::board::{Board, BoardSize, MAX_SIDE};
+use crate::stones::{Stone, EMPTY};
+
+type Coordinates = (usize, usize);
+
+const NO_CHAIN: usize = usize::MAX;
+const LIBERTY_WORDS: usize = (MAX_SIDE * MAX_SIDE).div_ceil(64);
+
+// Bitset over the point indices of the board
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+struct LibertySet([u64; LIBERTY_WORDS]);
+
+impl LibertySet {
+    fn insert(&mut self, index: usize) {
+        self.0[index / 64] |= 1 << (index % 64);
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.0[index / 64] &= !(1 << (index % 64));
+    }
+
+    fn union_with(&mut self, other: &LibertySet) {
+        for (word, other_word) in self.0.iter_mut().zip(other.0.iter()) {
+            *word |= other_word;
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(i, word)| {
+            (0..64).filter(move |bit| word & (1 << bit) != 0).map(move |bit| i * 64 + bit)
+        })
+    }
+}
+
+/*
+    Chains of connected stones, kept up to date as stones are played and captured.
+
+    Every stone points directly to the representative stone of its chain, and the stones of a chain are
+    linked in a circular list so that merging two chains only relabels the smaller one.
+    Liberties and sizes are only meaningful for representatives.
+*/
+#[derive(Clone, PartialEq)]
+pub struct Chains {
+    shape: BoardSize,
+    colors: Vec<Stone>,
+    chain_of: Vec<usize>,
+    next_stone: Vec<usize>,
+    sizes: Vec<usize>,
+    liberties: Vec<LibertySet>,
+}
+
+impl Chains {
+    pub fn new(shape: BoardSize) -> Chains {
+        let points = shape.0 * shape.1;
+        Chains {
+            shape,
+            colors: vec![EMPTY; points],
+            chain_of: vec![NO_CHAIN; points],
+            next_stone: vec![NO_CHAIN; points],
+            sizes: vec![0; points],
+            liberties: vec![LibertySet::default(); points],
+        }
+    }
+
+    pub fn from_board(board: &Board) -> Chains {
+        let mut chains = Self::new(board.shape);
+        for (x, row) in board.rows().iter().enumerate() {
+            for (y, stone) in row.iter().enumerate() {
+                if stone.is_some() {
+                    chains.add_stone((x, y), *stone);
+                }
+            }
+        }
+        chains
+    }
+
+    fn index(&self, (x, y): Coordinates) -> usize {
+        x * self.shape.1 + y
+    }
+
+    fn coordinates(&self, index: usize) -> Coordinates {
+        (index / self.shape.1, index % self.shape.1)
+    }
+
+    fn neighbors(&self, index: usize) -> impl Iterator<Item = usize> {
+        let (height, width) = self.shape;
+        let (x, y) = (index / width, index % width);
+        [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ]
+        .into_iter()
+        .filter(move |&(i, j)| i < height && j < width)
+        .map(move |(i, j)| i * width + j)
+    }
+
+    fn chain_indices(&self, root: usize) -> Vec<usize> {
+        let mut indices = vec![root];
+        let mut current = self.next_stone[root];
+        while current != root {
+            indices.push(current);
+            current = self.next_stone[current];
+        }
+        indices
+    }
+
+    // Representative stone of the chain at coords, None if the point is empty
+    pub fn chain_id(&self, coords: Coordinates) -> Option<usize> {
+        let root = self.chain_of[self.index(coords)];
+        if root == NO_CHAIN {
+            None
+        } else {
+            Some(root)
+        }
+    }
+
+    // Coordinates of the representative stone of the chain at coords
+    pub fn anchor(&self, coords: Coordinates) -> Option<Coordinates> {
+        self.chain_id(coords).map(|root| self.coordinates(root))
+    }
+
+    pub fn stones(&self, coords: Coordinates) -> Vec<Coordinates> {
+        match self.chain_id(coords) {
+            Some(root) => self.chain_coordinates(root),
+            None => vec![],
+        }
+    }
+
+    pub fn liberties(&self, coords: Coordinates) -> Vec<Coordinates> {
+        match self.chain_id(coords) {
+            Some(root) => self.liberties[root].indices().map(|i| self.coordinates(i)).collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn liberty_count(&self, coords: Coordinates) -> usize {
+        match self.chain_id(coords) {
+            Some(root) => self.liberties[root].count(),
+            None => 0,
+        }
+    }
+
+    pub fn is_in_atari(&self, coords: Coordinates) -> bool {
+        self.liberty_count(coords) == 1
+    }
+
+    // Opponent chains that would lose their last liberty if stone was played at coords
+    pub fn captured_by(&self, coords: Coordinates, stone: Stone) -> Vec<usize> {
+        let index = self.index(coords);
+        let mut captured: Vec<usize> = vec![];
+        for neighbor in self.neighbors(index) {
+            let root = self.chain_of[neighbor];
+            if root != NO_CHAIN
+                && self.colors[neighbor] != stone
+                && self.liberties[root].count() == 1
+                && !captured.contains(&root)
+            {
+                captured.push(root);
+            }
+        }
+        captured
+    }
+
+    // Chains of the given color touching coords
+    pub fn adjacent_chains(&self, coords: Coordinates, stone: Stone) -> Vec<usize> {
+        let index = self.index(coords);
+        let mut adjacent: Vec<usize> = vec![];
+        for neighbor in self.neighbors(index) {
+            let root = self.chain_of[neighbor];
+            if root != NO_CHAIN && self.colors[neighbor] == stone && !adjacent.contains(&root) {
+                adjacent.push(root);
+            }
+        }
+        adjacent
+    }
+
+    // Liberties of the chain formed by playing stone at coords, captures aside
+    pub fn liberties_after(&self, coords: Coordinates, stone: Stone) -> usize {
+        let index = self.index(coords);
+        let mut liberties = LibertySet::default();
+        for neighbor in self.neighbors(index) {
+            let root = self.chain_of[neighbor];
+            if root == NO_CHAIN {
+                liberties.insert(neighbor);
+            } else if self.colors[neighbor] == stone {
+                liberties.union_with(&self.liberties[root]);
+            }
+        }
+        liberties.remove(index);
+        liberties.count()
+    }
+
+    // Whether playing stone at coords leaves its own chain without liberties, captures aside
+    pub fn is_suicide(&self, coords: Coordinates, stone: Stone) -> bool {
+        let index = self.index(coords);
+        self.neighbors(index).all(|neighbor| {
+            let root = self.chain_of[neighbor];
+            root != NO_CHAIN && (self.colors[neighbor] != stone || self.liberties[root].count() == 1)
+        })
+    }
+
+    pub fn chain_coordinates(&self, root: usize) -> Vec<Coordinates> {
+        self.chain_indices(root).into_iter().map(|i| self.coordinates(i)).collect()
+    }
+
+    // Places stone at coords and removes the opponent chains left without liberties.
+    // If the played chain has no liberty left afterwards, it is removed as well (suicide).
+    // Returns the coordinates of every removed stone
+    pub fn play(&mut self, coords: Coordinates, stone: Stone) -> Vec<Coordinates> {
+        let index = self.index(coords);
+        self.add_stone(coords, stone);
+        let mut captured: Vec<Coordinates> = vec![];
+        let opponent_neighbors: Vec<usize> = self
+            .neighbors(index)
+            .filter(|&neighbor| self.colors[neighbor].is_some() && self.colors[neighbor] != stone)
+            .collect();
+        for neighbor in opponent_neighbors {
+            let root = self.chain_of[neighbor];
+            if root != NO_CHAIN && self.liberties[root].count() == 0 {
+                captured.extend(self.remove_chain(root));
+            }
+        }
+        let own_root = self.chain_of[index];
+        if self.liberties[own_root].count() == 0 {
+            captured.extend(self.remove_chain(own_root));
+        }
+        captured
+    }
+
+    // Places a stone and merges it with its neighbours, without resolving captures
+    fn add_stone(&mut self, coords: Coordinates, stone: Stone) {
+        let index = self.index(coords);
+        self.colors[index] = stone;
+        self.chain_of[index] = index;
+        self.next_stone[index] = index;
+        self.sizes[index] = 1;
+        self.liberties[index] = LibertySet::default();
+
+        let neighbors: Vec<usize> = self.neighbors(index).collect();
+        for &neighbor in &neighbors {
+            let root = self.chain_of[neighbor];
+            if root == NO_CHAIN {
+                self.liberties[index].insert(neighbor);
+            } else {
+                self.liberties[root].remove(index);
+            }
+        }
+        for &neighbor in &neighbors {
+            if self.colors[neighbor] == stone {
+                self.merge(self.chain_of[index], self.chain_of[neighbor]);
+            }
+        }
+    }
+
+    fn merge(&mut self, first_root: usize, second_root: usize) {
+        if first_root == second_root {
+            return;
+        }
+        let (big, small) = if self.sizes[first_root] >= self.sizes[second_root] {
+            (first_root, second_root)
+        } else {
+            (second_root, first_root)
+        };
+        for index in self.chain_indices(small) {
+            self.chain_of[index] = big;
+        }
+        self.next_stone.swap(big, small);
+        self.sizes[big] += self.sizes[small];
+        let small_liberties = self.liberties[small];
+        self.liberties[big].union_with(&small_liberties);
+    }
+
+    // Removes a whole chain from the board and gives its points back as liberties to the neighbouring chains
+    pub fn remove_chain(&mut self, root: usize) -> Vec<Coordinates> {
+        let indices = self.chain_indices(root);
+        for &index in &indices {
+            self.colors[index] = EMPTY;
+            self.chain_of[index] = NO_CHAIN;
+            self.next_stone[index] = NO_CHAIN;
+        }
+        for &index in &indices {
+            let neighbor_roots: Vec<usize> = self
+                .neighbors(index)
+                .map(|neighbor| self.chain_of[neighbor])
+                .filter(|&neighbor_root| neighbor_root != NO_CHAIN)
+                .collect();
+            for neighbor_root in neighbor_roots {
+                self.liberties[neighbor_root].insert(index);
+            }
+        }
+        indices.into_iter().map(|i| self.coordinates(i)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stones::{BLACK_STONE, WHITE_STONE};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    type Grid = Vec<Vec<Stone>>;
+
+    fn grid_neighbors(grid: &Grid, (x, y): Coordinates) -> Vec<Coordinates> {
+        let (height, width) = (grid.len(), grid[0].len());
+        [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)]
+            .into_iter()
+            .filter(|&(i, j)| i < height && j < width)
+            .collect()
+    }
+
+    // Stones and liberties of the chain at coords, both sorted, found by flood fill
+    fn flood_fill(grid: &Grid, coords: Coordinates) -> (Vec<Coordinates>, Vec<Coordinates>) {
+        let stone = grid[coords.0][coords.1];
+        let mut stones = vec![coords];
+        let mut liberties: Vec<Coordinates> = vec![];
+        let mut i = 0;
+        while i < stones.len() {
+            for neighbor in grid_neighbors(grid, stones[i]) {
+                let neighbor_stone = grid[neighbor.0][neighbor.1];
+                if neighbor_stone == stone && !stones.contains(&neighbor) {
+                    stones.push(neighbor);
+                } else if neighbor_stone == EMPTY && !liberties.contains(&neighbor) {
+                    liberties.push(neighbor);
+                }
+            }
+            i += 1;
+        }
+        stones.sort();
+        liberties.sort();
+        (stones, liberties)
+    }
+
+    // Opponent chains captured by a stone placed on the grid, then whether its own chain is left without liberties
+    fn reference_captures(grid: &Grid, coords: Coordinates, stone: Stone) -> (Vec<Coordinates>, bool) {
+        let mut captured: Vec<Coordinates> = vec![];
+        for neighbor in grid_neighbors(grid, coords) {
+            let neighbor_stone = grid[neighbor.0][neighbor.1];
+            if neighbor_stone.is_some() && neighbor_stone != stone && !captured.contains(&neighbor) {
+                let (stones, liberties) = flood_fill(grid, neighbor);
+                if liberties.is_empty() {
+                    captured.extend(stones);
+                }
+            }
+        }
+        captured.sort();
+        let suicide = flood_fill(grid, coords).1.is_empty();
+        (captured, suicide)
+    }
+
+    fn reference_play(grid: &mut Grid, coords: Coordinates, stone: Stone) -> Vec<Coordinates> {
+        grid[coords.0][coords.1] = stone;
+        let (mut captured, _) = reference_captures(grid, coords, stone);
+        for (x, y) in &captured {
+            grid[*x][*y] = EMPTY;
+        }
+        let (own_stones, own_liberties) = flood_fill(grid, coords);
+        if own_liberties.is_empty() {
+            for (x, y) in &own_stones {
+                grid[*x][*y] = EMPTY;
+            }
+            captured.extend(own_stones);
+        }
+        captured.sort();
+        captured
+    }
+
+    fn sorted(mut coordinates: Vec<Coordinates>) -> Vec<Coordinates> {
+        coordinates.sort();
+        coordinates
+    }
+
+    fn check_against_reference(chains: &Chains, grid: &Grid) {
+        for (x, row) in grid.iter().enumerate() {
+            for (y, stone) in row.iter().enumerate() {
+                if stone.is_some() {
+                    let (stones, liberties) = flood_fill(grid, (x, y));
+                    assert_eq!(sorted(chains.stones((x, y))), stones);
+                    assert_eq!(sorted(chains.liberties((x, y))), liberties);
+                    assert_eq!(chains.liberty_count((x, y)), liberties.len());
+                    continue;
+                }
+                assert_eq!(chains.chain_id((x, y)), None);
+                for color in [BLACK_STONE, WHITE_STONE] {
+                    let mut played = grid.clone();
+                    played[x][y] = color;
+                    let (captured, suicide) = reference_captures(&played, (x, y), color);
+                    let captured_by: Vec<Coordinates> = chains
+                        .captured_by((x, y), color)
+                        .into_iter()
+                        .flat_map(|root| chains.chain_coordinates(root))
+                        .collect();
+                    assert_eq!(sorted(captured_by), captured);
+                    assert_eq!(chains.is_suicide((x, y), color), suicide);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chains_match_flood_fill_on_random_games() {
+        let mut captures = 0;
+        for (seed, shape) in [(1, (5, 5)), (2, (7, 9)), (3, (9, 9)), (4, (2, 3))] {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut chains = Chains::new(shape);
+            let mut grid: Grid = vec![vec![EMPTY; shape.1]; shape.0];
+            for _ in 0..3 * shape.0 * shape.1 {
+                let coords = (rng.random_range(0..shape.0), rng.random_range(0..shape.1));
+                if grid[coords.0][coords.1].is_some() {
+                    continue;
+                }
+                let stone = if rng.random_bool(0.5) { BLACK_STONE } else { WHITE_STONE };
+                let expected = reference_play(&mut grid, coords, stone);
+                assert_eq!(sorted(chains.play(coords, stone)), expected);
+                check_against_reference(&chains, &grid);
+                captures += expected.len();
+            }
+        }
+        // the games must go through captures for the comparison to mean anything
+        assert!(captures > 0);
+    }
+
+    #[test]
+    fn chains_built_from_a_board_match_played_chains() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let shape = (6, 8);
+        let mut chains = Chains::new(shape);
+        let mut board = Board::new(shape);
+        for _ in 0..60 {
+            let coords = (rng.random_range(0..shape.0), rng.random_range(0..shape.1));
+            if board[coords].is_none() {
+                let stone = if rng.random_bool(0.5) { BLACK_STONE } else { WHITE_STONE };
+                board.set(coords, stone);
+                for captured in chains.play(coords, stone) {
+                    board.set(captured, EMPTY);
+                }
+            }
+        }
+        let rebuilt = Chains::from_board(&board);
+        check_against_reference(&rebuilt, board.rows());
+        check_against_reference(&chains, board.rows());
+    }
+}
@@ -1,77 +1,222 @@
-use crate::board::{Board, SIDE};
-use crate::players::player::Player;
+use crate::board::{Board, BoardSize};
+use crate::chains::Chains;
+use crate::benson::{self, UnconditionalLife};
+use crate::life::{self, ChainStatus};
+use crate::handicap::{fixed_handicap_coordinates, HandicapPlacement, MIN_HANDICAP};
+use crate::players::player::{Action, DeadStonesResponse, Player};
+use crate::point::Point;
 use crate::signals::*;
 use crate::stones::{
     Stone, BLACK_STONE, BLACK_STONE_CHAR, EMPTY, EMPTY_CHAR, WHITE_STONE, WHITE_STONE_CHAR,
 };
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::{Display, Error, Formatter};
 use std::rc::Rc;
+use std::time::SystemTime;
 use crate::players::human::Human;
-
-const KO_LENGTH: usize = 2;
+use crate::rules::{KoRule, Rules, Scoring};
+use crate::zobrist;
 
 pub type Coordinates = (usize, usize);
-pub type Move = Option<Coordinates>;
 
-pub type Group = HashMap<Coordinates, Stone>;
-pub type GroupDict = HashMap<&'static str, Group>;
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Move {
+    Play(Coordinates),
+    #[default]
+    Pass,
+    Resign,
+}
+
+// A move as it was played, with what it captured and how it was commented
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveRecord {
+    pub color: Stone,
+    pub played_move: Move,
+    // stones removed from the board by the move, own stones included for a suicide
+    pub captured: Vec<Coordinates>,
+    pub time: SystemTime,
+    pub comment: Option<String>,
+    // win rate of the player who moved, as estimated by the player or an analysis tool
+    pub evaluation: Option<f32>,
+}
+
+// Stones added, replaced or removed in the middle of the game, as SGF setup properties do
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetupRecord {
+    // number of moves played before the setup
+    pub move_number: usize,
+    pub stones: Vec<(Coordinates, Stone)>,
+    pub to_play: Stone,
+}
+
+// Outcome of a finished game
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameResult {
+    /// Stone of the winner and the margin of victory
+    Score(Stone, f32),
+    Draw,
+    /// Stone of the winner, whose opponent resigned
+    Resignation(Stone),
+}
+
+impl Display for GameResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let color = |stone: &Stone| if *stone == BLACK_STONE { "B" } else { "W" };
+        match self {
+            GameResult::Score(winner, margin) => write!(f, "{}+{}", color(winner), margin),
+            GameResult::Draw => write!(f, "0"),
+            GameResult::Resignation(winner) => write!(f, "{}+R", color(winner)),
+        }
+    }
+}
+// Number of counter proposals players may exchange about dead stones before play resumes
+const MAX_DEAD_STONES_DISPUTES: usize = 4;
+
+// Hash of a board position along with the stone of the player to move in it
+pub type PositionKey = (u64, Stone);
+
+// Stones connected to each other, along with their liberties
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chain {
+    pub color: Stone,
+    // representative stone, identifies the chain as long as it stays on the board
+    pub anchor: Coordinates,
+    pub stones: Vec<Coordinates>,
+    pub liberties: Vec<Coordinates>,
+    // anchors of the opponent chains touching this one
+    pub adjacent_opponent_chains: Vec<Coordinates>,
+}
+
+// Empty points connected to each other, along with the stones around them
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub points: Vec<Coordinates>,
+    pub border: Vec<Coordinates>,
+    pub borders_black: bool,
+    pub borders_white: bool,
+}
+
+impl Region {
+    // Color of the stones surrounding the region if they all belong to one player
+    pub fn owner(&self) -> Option<Stone> {
+        match (self.borders_black, self.borders_white) {
+            (true, false) => Some(BLACK_STONE),
+            (false, true) => Some(WHITE_STONE),
+            _ => None,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.points.len()
+    }
+}
+
+// Position the game started from, after the handicap stones, from which moves are replayed on undo
+#[derive(Clone, Default, PartialEq)]
+struct StartPosition {
+    board: Board,
+    position_history: Vec<PositionKey>,
+    prisoners: HashMap<Stone, usize>,
+    current_player: usize,
+    last_turned_passed: bool,
+}
 
 #[derive(Clone, PartialEq)]
 pub struct Game {
-    board_size: usize,
     board: Board,
-    last_boards: [Board; KO_LENGTH],
+    chains: Chains,
+    position_history: Vec<PositionKey>,
+    rules: Rules,
+    handicap: usize,
+    // stones captured by each player
+    prisoners: HashMap<Stone, usize>,
+    // stones agreed to be dead at the end of the game, removed before scoring
+    dead_stones: HashSet<Coordinates>,
     players: [Rc<Box<dyn Player>>; 2],
     current_player: usize,
     display: bool,
     last_turned_passed: bool,
-    komi: f32,
     is_over: bool,
+    result: Option<GameResult>,
+    start: StartPosition,
+    // every move played since the start position, passes and resignation included
+    records: Vec<MoveRecord>,
+    // setups made between the moves since the start position
+    setups: Vec<SetupRecord>,
+    // moves taken back, most recent last, until another move is played
+    undone_records: Vec<MoveRecord>,
 }
 
 impl Game {
     pub fn new(
         players: [Rc<Box<dyn Player>>; 2],
+        board_shape: BoardSize,
         display: bool,
-        komi: f32,
-    ) -> Self {
-        let board = Board::new();
-        let last_boards = [board.clone(), board.clone()];
-        Game {
-            board_size:SIDE,
+        rules: Rules,
+    ) -> Result<Self, Signals> {
+        // sizes come from players and files, so they are checked before the board is built
+        if !Board::is_valid_shape(board_shape) {
+            return Err(Signals::InvalidBoardSize);
+        }
+        let board = Board::new(board_shape);
+        let position_history = vec![(board.position_hash(), players[0].get_stone())];
+        let mut game = Game {
+            chains: Chains::new(board_shape),
             board,
-            last_boards,
+            position_history,
+            rules,
+            handicap: 0,
+            prisoners: HashMap::from([(BLACK_STONE, 0), (WHITE_STONE, 0)]),
+            dead_stones: HashSet::new(),
             players,
             current_player: 0,
             display,
             last_turned_passed: false,
-            komi,
             is_over: false,
-        }
+            result: None,
+            start: StartPosition::default(),
+            records: vec![],
+            setups: vec![],
+            undone_records: vec![],
+        };
+        game.mark_start();
+        Ok(game)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn from(
         board: Board,
-        last_boards: [Board; KO_LENGTH],
+        position_history: Vec<PositionKey>,
+        rules: Rules,
+        handicap: usize,
+        prisoners: HashMap<Stone, usize>,
         players: [Rc<Box<dyn Player>>; 2],
         display: bool,
         current_player: usize,
         last_turned_passed: bool,
-        komi: f32,
         is_over: bool,
     ) -> Self {
-        Game {
-            board_size: SIDE,
+        let mut game = Game {
+            chains: Chains::from_board(&board),
             board,
-            last_boards,
+            position_history,
+            rules,
+            handicap,
+            prisoners,
+            dead_stones: HashSet::new(),
             players,
             current_player,
             display,
             last_turned_passed,
-            komi,
             is_over,
-        }
+            result: None,
+            start: StartPosition::default(),
+            records: vec![],
+            setups: vec![],
+            undone_records: vec![],
+        };
+        game.mark_start();
+        game
     }
 
     pub fn get(&self, x: usize, y: usize) -> &Stone {
@@ -79,11 +224,15 @@ impl Game {
     }
 
     pub fn width(&self) -> &usize {
-        &self.board.shape.0
+        &self.board.shape.1
     }
 
     pub fn height(&self) -> &usize {
-        &self.board.shape.1
+        &self.board.shape.0
+    }
+
+    pub fn shape(&self) -> BoardSize {
+        self.board.shape
     }
 
     pub fn get_board(&self) -> &Board {
@@ -94,11 +243,33 @@ impl Game {
         self.is_over
     }
 
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+
+    // Ends the game with a result decided outside of play, like the one of a game record
+    pub fn set_result(&mut self, result: GameResult) {
+        self.is_over = true;
+        self.result = Some(result);
+    }
+
+    // Lets the players continue after both passed, e.g. when they disagree on dead stones
+    pub fn resume_play(&mut self) {
+        self.is_over = false;
+        self.result = None;
+        self.last_turned_passed = false;
+        self.dead_stones.clear();
+    }
+
+    pub fn set_display(&mut self, display: bool) {
+        self.display = display
+    }
+
     pub fn get_current_player(&self) -> Rc<Box<dyn Player>> {
         Rc::clone(&self.players[self.current_player])
     }
 
-    fn find_player(&self, stone: Stone) -> Rc<Box<dyn Player>> {
+    pub fn find_player(&self, stone: Stone) -> Rc<Box<dyn Player>> {
         Rc::clone(self.players
             .iter()
             .find(|player| player.get_stone() == stone)
@@ -110,8 +281,226 @@ impl Game {
         self.get_board()[(x, y)] != EMPTY
     }
 
-    fn check_ko(&self, board: &Board) -> bool {
-        self.last_boards[self.current_player] == *board
+    // Zobrist hash of the position including the player to move, usable as a transposition key
+    pub fn position_hash(&self) -> u64 {
+        self.board.position_hash() ^ zobrist::side_to_move_key(self.get_current_player().get_stone())
+    }
+
+    // Keys of every position of the game so far, the current one last
+    pub fn position_history(&self) -> &[PositionKey] {
+        &self.position_history
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules
+    }
+
+    pub fn komi(&self) -> f32 {
+        self.rules.komi
+    }
+
+    pub fn set_komi(&mut self, komi: f32) {
+        self.rules.komi = komi
+    }
+
+    pub fn handicap(&self) -> usize {
+        self.handicap
+    }
+
+    pub fn place_handicap(&mut self, handicap: usize, placement: HandicapPlacement) -> Result<(), Signals> {
+        match placement {
+            HandicapPlacement::Fixed => self.set_fixed_handicap(handicap).map(|_| ()),
+            HandicapPlacement::Free => {
+                let black_player = self.find_player(BLACK_STONE);
+                let stones = black_player.choose_handicap_stones(self, handicap);
+                if stones.len() != handicap {
+                    return Err(Signals::InvalidHandicap);
+                }
+                self.set_free_handicap(&stones)
+            }
+        }
+    }
+
+    pub fn set_fixed_handicap(&mut self, handicap: usize) -> Result<Vec<Coordinates>, Signals> {
+        let stones = fixed_handicap_coordinates(self.shape(), handicap).ok_or(Signals::InvalidHandicap)?;
+        self.set_free_handicap(&stones)?;
+        Ok(stones)
+    }
+
+    // Puts black stones on the given points of an empty board, White then plays first
+    pub fn set_free_handicap(&mut self, stones: &[Coordinates]) -> Result<(), Signals> {
+        let (height, width) = self.shape();
+        let is_empty_start = self.position_history.len() == 1
+            && self.board.rows().iter().flatten().all(|stone| stone.is_none());
+        let distinct_stones: HashSet<&Coordinates> = stones.iter().collect();
+        if !is_empty_start
+            || stones.len() < MIN_HANDICAP
+            || stones.len() >= height * width
+            || distinct_stones.len() != stones.len()
+            || stones.iter().any(|coords| !self.board.is_on_board(*coords))
+        {
+            return Err(Signals::InvalidHandicap);
+        }
+        for coords in stones {
+            self.chains.play(*coords, BLACK_STONE);
+            self.board.set(*coords, BLACK_STONE);
+        }
+        self.handicap = stones.len();
+        self.current_player = self.players.iter().position(|player| player.get_stone() == WHITE_STONE).unwrap();
+        self.position_history = vec![(self.board.position_hash(), WHITE_STONE)];
+        self.mark_start();
+        Ok(())
+    }
+
+    /*
+        Adds, replaces or removes stones outside of play, as SGF setup properties do, then gives the turn to `to_play`.
+        The result becomes the start position: moves played before cannot be taken back any more.
+        Nothing changes if a point is outside the board or a chain is left without liberties
+    */
+    pub fn set_stones(&mut self, stones: &[(Coordinates, Stone)], to_play: Stone) -> Result<(), Signals> {
+        self.place_stones(stones, to_play)?;
+        self.position_history = vec![(self.board.position_hash(), to_play)];
+        self.mark_start();
+        Ok(())
+    }
+
+    /*
+        Same as set_stones, except that the moves played before are kept: the setup is recorded after them
+        and undo replays it. Before the first move, the setup becomes the start position
+    */
+    pub fn add_setup(&mut self, stones: &[(Coordinates, Stone)], to_play: Stone) -> Result<(), Signals> {
+        if self.records.is_empty() {
+            return self.set_stones(stones, to_play);
+        }
+        self.place_stones(stones, to_play)?;
+        self.position_history.push((self.board.position_hash(), to_play));
+        self.setups.push(SetupRecord { move_number: self.records.len(), stones: stones.to_vec(), to_play });
+        self.undone_records.clear();
+        Ok(())
+    }
+
+    fn place_stones(&mut self, stones: &[(Coordinates, Stone)], to_play: Stone) -> Result<(), Signals> {
+        if stones.iter().any(|(coords, _)| !self.board.is_on_board(*coords)) {
+            return Err(Signals::OutsideBounds);
+        }
+        let mut board = self.board.clone();
+        for (coords, stone) in stones {
+            board.set(*coords, *stone);
+        }
+        let chains = Chains::from_board(&board);
+        let (height, width) = board.shape;
+        for x in 0..height {
+            for y in 0..width {
+                if board[(x, y)].is_some() && chains.liberty_count((x, y)) == 0 {
+                    return Err(Signals::InvalidSetup);
+                }
+            }
+        }
+        self.board = board;
+        self.chains = chains;
+        self.current_player = self.players.iter().position(|player| player.get_stone() == to_play).unwrap();
+        self.last_turned_passed = false;
+        self.resume_play();
+        Ok(())
+    }
+
+    pub fn prisoners(&self, stone: Stone) -> usize {
+        self.prisoners[&stone]
+    }
+
+    pub fn dead_stones(&self) -> &HashSet<Coordinates> {
+        &self.dead_stones
+    }
+
+    // Marks the chains containing the given stones as dead, replacing any previous marking
+    pub fn set_dead_stones(&mut self, stones: &[Coordinates]) {
+        self.dead_stones.clear();
+        for coords in stones {
+            if self.board.is_on_board(*coords) {
+                self.dead_stones.extend(self.chains.stones(*coords));
+            }
+        }
+    }
+
+    // Stones of the player that cannot be captured even if they pass every turn, with the points they own
+    pub fn unconditional_life(&self, color: Stone) -> UnconditionalLife {
+        benson::unconditional_life(self, color)
+    }
+
+    // Points inside territory owned unconditionally by either player, where playing is pointless
+    pub fn settled_points(&self) -> Vec<Vec<bool>> {
+        let (height, width) = self.shape();
+        let mut settled = vec![vec![false; width]; height];
+        for color in [BLACK_STONE, WHITE_STONE] {
+            for (x, y) in self.unconditional_life(color).territory {
+                settled[x][y] = true;
+            }
+        }
+        settled
+    }
+
+    // Stones standing inside the unconditional territory of their opponent, which can never live
+    pub fn unconditionally_dead_stones(&self) -> Vec<Coordinates> {
+        let mut dead: Vec<Coordinates> = vec![];
+        for color in [BLACK_STONE, WHITE_STONE] {
+            for (x, y) in self.unconditional_life(color).territory {
+                if self.board[(x, y)].is_some() && self.board[(x, y)] != color {
+                    dead.push((x, y));
+                }
+            }
+        }
+        dead
+    }
+
+    // Marks the stones the life and death estimator considers dead, so that scoring ignores them
+    pub fn mark_estimated_dead_stones(&mut self, playouts: u32) {
+        let estimates = life::estimate_life_and_death(self, playouts);
+        self.set_dead_stones(&life::dead_stones(&estimates));
+    }
+
+    fn next_player_stone(&self) -> Stone {
+        self.players[1 - self.current_player].get_stone()
+    }
+
+    pub fn liberty_count(&self, coords: Coordinates) -> usize {
+        self.chains.liberty_count(coords)
+    }
+
+    pub fn is_in_atari(&self, coords: Coordinates) -> bool {
+        self.chains.is_in_atari(coords)
+    }
+
+    fn check_ko(&self, position_hash: u64) -> Result<(), Signals> {
+        match self.rules.ko_rule {
+            KoRule::Simple => {
+                // the position before the opponent's last move is two entries back in the history
+                let history_length = self.position_history.len();
+                if history_length >= 2 && self.position_history[history_length - 2].0 == position_hash {
+                    return Err(Signals::BreakingKo);
+                }
+            }
+            KoRule::PositionalSuperko => {
+                if self.position_history.iter().any(|(hash, _)| *hash == position_hash) {
+                    return Err(Signals::BreakingSuperko);
+                }
+            }
+            KoRule::SituationalSuperko => {
+                let position_key = (position_hash, self.next_player_stone());
+                if self.position_history.contains(&position_key) {
+                    return Err(Signals::BreakingSuperko);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn record_position(&mut self) {
+        let position_key = (self.board.position_hash(), self.get_current_player().get_stone());
+        self.position_history.push(position_key);
     }
 
     pub fn game(&mut self) {
@@ -120,8 +509,19 @@ impl Game {
         }
         loop {
             let current_player = self.get_current_player();
-            let current_player_choice = current_player.choose_case(&self);
+            let current_player_choice = match current_player.choose_action(self) {
+                Action::Move(chosen_move) => chosen_move,
+                Action::Undo => {
+                    self.take_back(current_player.get_stone());
+                    continue;
+                }
+            };
             let step_result = self.step(current_player_choice);
+            if Self::is_recorded(&step_result) {
+                if let Some(evaluation) = current_player.last_evaluation() {
+                    self.set_last_evaluation(evaluation);
+                }
+            }
 
             match step_result {
                 Ok(()) => continue,
@@ -129,24 +529,44 @@ impl Game {
                     Signals::InducesSuicide
                     | Signals::OccupiedCase
                     | Signals::BreakingKo
-                    | Signals::OutsideBounds => {
+                    | Signals::BreakingSuperko
+                    | Signals::InvalidHandicap
+                    | Signals::InvalidBoardSize
+                    | Signals::OutsideBounds
+                    | Signals::InvalidSetup => {
                         if self.display {
                             println!("{}", e)
                         }
                     }
 
+                    Signals::Resignation => {
+                        if self.display {
+                            println!("{} resigns", current_player);
+                        }
+                        break;
+                    }
+
                     Signals::GameOver | Signals::DoublePass => {
                         if self.display{
                             println!("{}", e);
                         }
-                        self.is_over = true;
-                        break;
+                        if self.end_play() {
+                            break;
+                        }
                     }
                 },
             }
         }
+        if self.result.is_none() {
+            self.result = Some(self.scored_result());
+        }
         let winner = self.winner();
         if self.display {
+            if let Some(GameResult::Score(..) | GameResult::Draw) = self.result {
+                let scores = self.calculate_scores();
+                println!("Black score : {}", scores[&BLACK_STONE]);
+                println!("White score : {}", scores[&WHITE_STONE]);
+            }
             match winner {
                 Some(player) => {
                     println!("Winner: {}", player);
@@ -158,142 +578,357 @@ impl Game {
         }
     }
 
+    // Undoes moves until the given player is to move again, e.g. the opponent's answer and the player's last move
+    fn take_back(&mut self, stone: Stone) {
+        if self.undo().is_none() {
+            if self.display {
+                println!("No move to take back");
+            }
+            return;
+        }
+        while self.get_current_player().get_stone() != stone && self.undo().is_some() {}
+        if self.display {
+            println!("{}", self);
+        }
+    }
+
+    /*
+        Both players passed: the game is over if they agree on the dead stones,
+        otherwise play resumes so that they settle the disputed groups on the board.
+        Returns whether the game is over
+    */
+    fn end_play(&mut self) -> bool {
+        self.is_over = true;
+        if self.agree_on_dead_stones() {
+            return true;
+        }
+        if self.display {
+            println!("Players do not agree on dead stones, play resumes");
+        }
+        self.resume_play();
+        false
+    }
+
+    /*
+        Scoring phase: a player proposes which stones are dead, the other accepts the proposal or disputes it
+        with a counter proposal, which is then reviewed by the first player, and so on.
+        Returns false if no agreement was reached
+    */
+    fn agree_on_dead_stones(&mut self) -> bool {
+        // the player who passed last proposes, the other one reviews
+        let mut reviewer = self.current_player;
+        let proposer = Rc::clone(&self.players[1 - reviewer]);
+        let mut proposal = proposer.propose_dead_stones(self);
+        for _ in 0..MAX_DEAD_STONES_DISPUTES {
+            let reviewing_player = Rc::clone(&self.players[reviewer]);
+            match reviewing_player.review_dead_stones(self, &proposal) {
+                DeadStonesResponse::Accept => {
+                    self.set_dead_stones(&proposal);
+                    return true;
+                }
+                DeadStonesResponse::Dispute(counter_proposal) => {
+                    if self.display {
+                        println!("{} disputes the dead stones", reviewing_player);
+                    }
+                    proposal = counter_proposal;
+                    reviewer = 1 - reviewer;
+                }
+            }
+        }
+        false
+    }
+
+    // Plays a move of the current player, forgetting the moves that were taken back
     pub fn step(&mut self, player_choice: Move) -> Result<(), Signals> {
+        let step_result = self.apply_move(player_choice);
+        if Self::is_recorded(&step_result) {
+            self.undone_records.clear();
+        }
+        step_result
+    }
+
+    // Whether a step with that result changed the game and belongs to the move history
+    fn is_recorded(step_result: &Result<(), Signals>) -> bool {
+        matches!(step_result, Ok(()) | Err(Signals::DoublePass) | Err(Signals::Resignation))
+    }
+
+    fn apply_move(&mut self, player_choice: Move) -> Result<(), Signals> {
+        let color = self.get_current_player().get_stone();
+        let step_result = self.resolve_move(player_choice);
+        let captured = match &step_result {
+            Ok(captured) => captured.clone(),
+            _ => vec![],
+        };
+        let step_result = step_result.map(|_| ());
+        if Self::is_recorded(&step_result) {
+            self.records.push(MoveRecord {
+                color,
+                played_move: player_choice,
+                captured,
+                time: SystemTime::now(),
+                comment: None,
+                evaluation: None,
+            });
+        }
+        step_result
+    }
+
+    // Plays the move and returns the stones it removed from the board
+    fn resolve_move(&mut self, player_choice: Move) -> Result<Vec<Coordinates>, Signals> {
+        if self.is_over {
+            return Err(Signals::GameOver);
+        }
         match player_choice {
-            None => {
-                if self.last_turned_passed {
+            Move::Resign => {
+                self.is_over = true;
+                self.result = Some(GameResult::Resignation(self.next_player_stone()));
+                Err(Signals::Resignation)
+            }
+
+            Move::Pass => {
+                let is_double_pass = self.last_turned_passed;
+                self.last_turned_passed = true;
+                match self.current_player {
+                    0 => self.current_player = 1,
+                    1 => self.current_player = 0,
+                    _ => panic!("Current player count is outside of bounds"),
+                }
+                self.record_position();
+                if is_double_pass {
+                    // if play resumes, the opponent of the last player who passed moves first
                     self.is_over = true;
-                    Err(Signals::DoublePass)
-                } else {
-                    self.last_turned_passed = true;
-                    match self.current_player {
-                        0 => self.current_player = 1,
-                        1 => self.current_player = 0,
-                        _ => panic!("Current player count is outside of bounds"),
-                    }
-                    if self.display{
-                        println!("{}", self);
-                    }
-                    Ok(())
+                    return Err(Signals::DoublePass);
+                }
+                if self.display{
+                    println!("{}", self);
                 }
+                Ok(vec![])
             }
 
-            Some(chosen_coords) => {
-                let board_result = self.verify_player_choice(chosen_coords)?;
-                self.last_boards[self.current_player] = board_result.clone();
-
-                self.board = board_result;
+            Move::Play(chosen_coords) => {
+                self.verify_player_choice(chosen_coords)?;
+                let player_stone = self.get_current_player().get_stone();
+                let captured = self.place_stone(chosen_coords, player_stone);
                 self.last_turned_passed = false;
                 match self.current_player {
                     0 => self.current_player = 1,
                     1 => self.current_player = 0,
                     _ => panic!("Current player count is not in bounds"),
                 }
+                self.record_position();
                 if self.display{
                     println!("{}", self);
                 }
-                Ok(())
+                Ok(captured)
             }
         }
     }
 
-    fn verify_player_choice(&mut self, player_choice: Coordinates) -> Result<Board, Signals> {
-        let (x, y) = player_choice;
-        if x >= self.board_size || y >= self.board_size {
-            return Err(Signals::OutsideBounds);
+    // Position before the first recorded move, with the handicap or setup stones
+    pub fn start_board(&self) -> &Board {
+        &self.start.board
+    }
+
+    pub fn start_player(&self) -> Stone {
+        self.players[self.start.current_player].get_stone()
+    }
+
+    // Every move played since the start position, oldest first
+    pub fn records(&self) -> &[MoveRecord] {
+        &self.records
+    }
+
+    pub fn setups(&self) -> &[SetupRecord] {
+        &self.setups
+    }
+
+    pub fn last_record(&self) -> Option<&MoveRecord> {
+        self.records.last()
+    }
+
+    pub fn moves(&self) -> impl Iterator<Item = Move> + '_ {
+        self.records.iter().map(|record| record.played_move)
+    }
+
+    // Number of moves played since the start position, passes included
+    pub fn move_number(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn set_last_comment(&mut self, comment: &str) {
+        if let Some(record) = self.records.last_mut() {
+            record.comment = Some(comment.to_string());
         }
-        if self.is_case_occupied(player_choice) {
-            return Err(Signals::OccupiedCase);
+    }
+
+    pub fn set_last_evaluation(&mut self, evaluation: f32) {
+        if let Some(record) = self.records.last_mut() {
+            record.evaluation = Some(evaluation);
         }
+    }
 
-        let induces_suicide = self.induces_suicide(player_choice);
-        let captured_groups = self.groups_to_capture(player_choice);
-        let induces_capture = captured_groups.len() > 0;
+    pub fn can_undo(&self) -> bool {
+        !self.records.is_empty()
+    }
 
-        if induces_suicide {
-            if !induces_capture {
-                return Err(Signals::InducesSuicide);
+    pub fn can_redo(&self) -> bool {
+        !self.undone_records.is_empty()
+    }
+
+    /*
+        Takes back the last move by replaying every other move from the start position,
+        which restores captures, the position history used for ko, pass flags and the player to move.
+        Setups made after the last move are taken back with it.
+        Returns the move taken back, None if no move was played
+    */
+    pub fn undo(&mut self) -> Option<Move> {
+        let last_record = self.records.pop()?;
+        let records = std::mem::take(&mut self.records);
+        let move_number = records.len();
+        self.setups.retain(|setup| setup.move_number <= move_number);
+        let display = self.display;
+        self.display = false;
+        self.restore_start();
+        for (i, record) in records.into_iter().enumerate() {
+            self.replay_setups(i);
+            // play went on after a double pass when players disagreed on dead stones
+            if self.is_over {
+                self.resume_play();
             }
+            self.replay(record);
         }
+        self.replay_setups(move_number);
+        self.display = display;
+        let last_move = last_record.played_move;
+        self.undone_records.push(last_record);
+        Some(last_move)
+    }
 
-        let mut new_board = self.board.clone();
-        new_board[player_choice] = self.get_current_player().get_stone();
+    // Plays again the last move taken back, None if there is none
+    pub fn redo(&mut self) -> Option<Move> {
+        let undone_record = self.undone_records.pop()?;
+        let undone_move = undone_record.played_move;
+        let display = self.display;
+        self.display = false;
+        if self.is_over {
+            self.resume_play();
+        }
+        self.replay(undone_record);
+        self.display = display;
+        Some(undone_move)
+    }
 
-        if induces_capture {
-            Self::capture_groups(&mut new_board, captured_groups);
-            if self.check_ko(&new_board) {
-                Err(Signals::BreakingKo)
-            } else {
-                Ok(new_board)
+    // Plays a recorded move again, keeping its time stamp and annotations
+    fn replay(&mut self, record: MoveRecord) {
+        if !Self::is_recorded(&self.apply_move(record.played_move)) {
+            panic!("Recorded move cannot be played again");
+        }
+        *self.records.last_mut().unwrap() = record;
+    }
+
+    // Makes again the setups recorded after the given number of moves
+    fn replay_setups(&mut self, move_number: usize) {
+        let setups: Vec<SetupRecord> =
+            self.setups.iter().filter(|setup| setup.move_number == move_number).cloned().collect();
+        for setup in setups {
+            if self.place_stones(&setup.stones, setup.to_play).is_err() {
+                panic!("Recorded setup cannot be made again");
             }
-        } else {
-            Ok(new_board)
+            self.position_history.push((self.board.position_hash(), setup.to_play));
         }
     }
 
-    fn induces_suicide(&self, player_choice: Coordinates) -> bool {
-        let mut test_board = self.board.clone();
-        test_board[player_choice] = self.get_current_player().get_stone();
-        let opposite_stone: Stone = Some(!self.get_current_player().get_stone().unwrap());
-        let same_stone_group_dicts = Self::flood_fill(player_choice, &test_board, true, false);
-        same_stone_group_dicts
-            .get("border")
-            .unwrap()
-            .values()
-            .all(|stone| *stone == opposite_stone)
+    fn restore_start(&mut self) {
+        self.board = self.start.board.clone();
+        self.chains = Chains::from_board(&self.board);
+        self.position_history = self.start.position_history.clone();
+        self.prisoners = self.start.prisoners.clone();
+        self.current_player = self.start.current_player;
+        self.last_turned_passed = self.start.last_turned_passed;
+        self.resume_play();
     }
 
-    fn groups_to_capture(&mut self, player_choice: Coordinates) -> Vec<GroupDict> {
-        let mut test_board = self.board.clone();
+    // Makes the current position the one moves are replayed from
+    fn mark_start(&mut self) {
+        self.start = StartPosition {
+            board: self.board.clone(),
+            position_history: self.position_history.clone(),
+            prisoners: self.prisoners.clone(),
+            current_player: self.current_player,
+            last_turned_passed: self.last_turned_passed,
+        };
+        self.records.clear();
+        self.setups.clear();
+        self.undone_records.clear();
+    }
+
+    fn verify_player_choice(&self, player_choice: Coordinates) -> Result<(), Signals> {
+        if !self.board.is_on_board(player_choice) {
+            return Err(Signals::OutsideBounds);
+        }
+        if self.is_case_occupied(player_choice) {
+            return Err(Signals::OccupiedCase);
+        }
+
         let player_stone = self.get_current_player().get_stone();
-        test_board[player_choice] = player_stone;
-        let neighbors = Self::neighbors_indices(player_choice, &test_board);
-        let opposite_stone = Some(!self.get_current_player().get_stone().unwrap());
-        let opposite_stone_neighbors_coords: Vec<Coordinates> = neighbors
-            .into_iter()
-            .filter(|&coordinates| test_board[coordinates] == opposite_stone)
-            .collect();
+        let captured_chains = self.chains.captured_by(player_choice, player_stone);
+        let induces_capture = !captured_chains.is_empty();
 
-        let mut opposite_stone_neighbors_group_dicts: Vec<GroupDict> =
-            opposite_stone_neighbors_coords
-                .into_iter()
-                .map(|coordinates| Self::flood_fill(coordinates, &test_board, true, false))
-                .collect();
+        let induces_suicide = !induces_capture && self.chains.is_suicide(player_choice, player_stone);
 
-        opposite_stone_neighbors_group_dicts
-            .iter_mut()
-            .for_each(|group_dict| self.mark_capture(group_dict));
+        // rules allowing suicide only allow it for several stones, a lone stone would change nothing but the turn
+        let is_single_stone = self.chains.adjacent_chains(player_choice, player_stone).is_empty();
+        if induces_suicide && (!self.rules.suicide_allowed || is_single_stone) {
+            return Err(Signals::InducesSuicide);
+        }
 
-        let opposite_stone_captured_groups = opposite_stone_neighbors_group_dicts
-            .into_iter()
-            .filter(|group_dict| group_dict.contains_key("captured"))
-            .collect::<Vec<GroupDict>>();
+        // hash of the resulting position, computed without playing the move
+        let opposite_stone = Some(!player_stone.unwrap());
+        let mut position_hash = self.board.position_hash();
+        if induces_suicide {
+            // the played stone and the chains it connects to are all removed
+            for chain in self.chains.adjacent_chains(player_choice, player_stone) {
+                for coords in self.chains.chain_coordinates(chain) {
+                    position_hash ^= zobrist::stone_key(coords, player_stone);
+                }
+            }
+        } else {
+            position_hash ^= zobrist::stone_key(player_choice, player_stone);
+            for chain in captured_chains {
+                for coords in self.chains.chain_coordinates(chain) {
+                    position_hash ^= zobrist::stone_key(coords, opposite_stone);
+                }
+            }
+        }
+        self.check_ko(position_hash)?;
+        Ok(())
+    }
 
-        opposite_stone_captured_groups
+    // Places a stone and removes the chains left without liberties, returns the removed stones
+    fn place_stone(&mut self, coords: Coordinates, stone: Stone) -> Vec<Coordinates> {
+        let removed = self.chains.play(coords, stone);
+        self.board.set(coords, stone);
+        for removed_coords in &removed {
+            // removed stones are prisoners of the opponent of their owner, suicided ones included
+            let removed_stone = self.board[*removed_coords];
+            *self.prisoners.get_mut(&Some(!removed_stone.unwrap())).unwrap() += 1;
+            self.board.set(*removed_coords, EMPTY);
+        }
+        removed
     }
 
-    fn mark_capture(&self, group_dict: &mut GroupDict) {
+    // Whether the move connects to other stones of the player into a chain left in atari, without capturing
+    pub fn is_self_atari(&self, player_choice: Coordinates) -> bool {
         let player_stone = self.get_current_player().get_stone();
-        if group_dict
-            .get("border")
-            .unwrap()
-            .values()
-            .collect::<Vec<&Stone>>()
-            .iter()
-            .all(|stone_value| **stone_value == player_stone)
-        {
-            group_dict.insert("captured", HashMap::new());
-        }
+        !self.chains.adjacent_chains(player_choice, player_stone).is_empty()
+            && self.chains.liberties_after(player_choice, player_stone) <= 1
+            && self.chains.captured_by(player_choice, player_stone).is_empty()
     }
 
-    fn capture_groups(board: &mut Board, opposite_stone_neighbors_group_dicts: Vec<GroupDict>) {
-        for group_dict in opposite_stone_neighbors_group_dicts {
-            let captured_group = group_dict.get("group").unwrap();
-            let captured_group_coords = captured_group.keys();
-            for coords in captured_group_coords {
-                board[*coords] = EMPTY
-            }
-        }
+    pub fn induces_suicide(&self, player_choice: Coordinates) -> bool {
+        let player_stone = self.get_current_player().get_stone();
+        self.chains.is_suicide(player_choice, player_stone)
+            && self.chains.captured_by(player_choice, player_stone).is_empty()
     }
 
     fn neighbors_indices((x, y): Coordinates, board: &Board) -> Vec<Coordinates> {
@@ -314,80 +949,100 @@ impl Game {
         neighbors
     }
 
-    fn flood_fill(
-        coords: Coordinates,
-        board: &Board,
-        add_border: bool,
-        add_empty: bool,
-    ) -> GroupDict {
-        let group: HashMap<Coordinates, Stone> = HashMap::new();
-        let border: HashMap<Coordinates, Stone> = HashMap::new();
-        let mut group_dict: GroupDict = HashMap::new();
-        group_dict.insert("group", group);
-        group_dict.insert("border", border);
-        let stone_of_origin = board[coords];
-
-        let mut queue: VecDeque<Coordinates> = VecDeque::new();
-        queue.push_back(coords);
-        while !queue.is_empty() {
-            let coords = queue.pop_front().unwrap();
-            if group_dict["group"].contains_key(&coords)
-                || group_dict["border"].contains_key(&coords)
-            {
-                continue;
-            } else {
-                match board[coords] {
-                    EMPTY => {
-                        // if flood fill is called on an empty case, it should behave like a normal call and
-                        // add every adjacent cases if they are empty as well
-                        if stone_of_origin == EMPTY {
-                            group_dict
-                                .get_mut("group")
-                                .unwrap()
-                                .insert(coords, stone_of_origin);
-                            if add_border {
-                                group_dict.get_mut("border").unwrap().insert(coords, EMPTY);
-                            }
-                        }
-                        else if add_empty {
-                            group_dict.get_mut("group").unwrap().insert(coords, EMPTY);
-                            let neighbors = Self::neighbors_indices(coords, &board);
-                            for neighbor in neighbors {
-                                queue.push_back(neighbor);
-                            }
-                        } else if add_border {
-                            group_dict.get_mut("border").unwrap().insert(coords, EMPTY);
-                        }
+    pub fn chain_at(&self, coords: Coordinates) -> Option<Chain> {
+        let anchor = self.chains.anchor(coords)?;
+        let color = self.board[coords];
+        let stones = self.chains.stones(coords);
+        let mut adjacent_opponent_chains: Vec<Coordinates> = vec![];
+        for stone in &stones {
+            for neighbor in Self::neighbors_indices(*stone, &self.board) {
+                if self.board[neighbor].is_some() && self.board[neighbor] != color {
+                    let neighbor_anchor = self.chains.anchor(neighbor).unwrap();
+                    if !adjacent_opponent_chains.contains(&neighbor_anchor) {
+                        adjacent_opponent_chains.push(neighbor_anchor);
                     }
-                    some_stone => {
-                        if some_stone == stone_of_origin {
-                            group_dict
-                                .get_mut("group")
-                                .unwrap()
-                                .insert(coords, some_stone);
-                            let neighbors = Self::neighbors_indices(coords, &board);
-                            for neighbor in neighbors {
-                                queue.push_back(neighbor);
-                            }
-                        } else {
-                            if add_border {
-                                group_dict
-                                    .get_mut("border")
-                                    .unwrap()
-                                    .insert(coords, some_stone);
-                            }
-                        }
+                }
+            }
+        }
+        Some(Chain {
+            color,
+            anchor,
+            stones,
+            liberties: self.chains.liberties(coords),
+            adjacent_opponent_chains,
+        })
+    }
+
+    pub fn region_at(&self, coords: Coordinates) -> Option<Region> {
+        if self.board[coords].is_some() {
+            return None;
+        }
+        let mut points: Vec<Coordinates> = vec![];
+        let mut border: Vec<Coordinates> = vec![];
+        let mut visited: HashSet<Coordinates> = HashSet::from([coords]);
+        let mut queue: VecDeque<Coordinates> = VecDeque::from([coords]);
+        while let Some(current) = queue.pop_front() {
+            points.push(current);
+            for neighbor in Self::neighbors_indices(current, &self.board) {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                if self.board[neighbor] == EMPTY {
+                    queue.push_back(neighbor);
+                } else {
+                    border.push(neighbor);
+                }
+            }
+        }
+        let borders_black = border.iter().any(|stone| self.board[*stone] == BLACK_STONE);
+        let borders_white = border.iter().any(|stone| self.board[*stone] == WHITE_STONE);
+        Some(Region {
+            points,
+            border,
+            borders_black,
+            borders_white,
+        })
+    }
+
+    pub fn chains(&self) -> Vec<Chain> {
+        let mut chains: Vec<Chain> = vec![];
+        let mut counted: HashSet<Coordinates> = HashSet::new();
+        let (height, width) = self.board.shape;
+        for i in 0..height {
+            for j in 0..width {
+                if !counted.contains(&(i, j)) {
+                    if let Some(chain) = self.chain_at((i, j)) {
+                        counted.extend(chain.stones.iter());
+                        chains.push(chain);
                     }
                 }
             }
         }
-        group_dict
+        chains
+    }
+
+    pub fn regions(&self) -> Vec<Region> {
+        let mut regions: Vec<Region> = vec![];
+        let mut counted: HashSet<Coordinates> = HashSet::new();
+        let (height, width) = self.board.shape;
+        for i in 0..height {
+            for j in 0..width {
+                if !counted.contains(&(i, j)) {
+                    if let Some(region) = self.region_at((i, j)) {
+                        counted.extend(region.points.iter());
+                        regions.push(region);
+                    }
+                }
+            }
+        }
+        regions
     }
 
     pub fn winner(&self) -> Option<Rc<Box<dyn Player>>> {
+        if let Some(GameResult::Resignation(winner)) = self.result {
+            return Some(self.find_player(winner));
+        }
         let scores = self.calculate_scores();
-        println!("Black score : {}", scores[&BLACK_STONE]);
-        println!("White score : {}", scores[&WHITE_STONE]);
         if scores[&BLACK_STONE] > scores[&WHITE_STONE] {
             Some(self.find_player(BLACK_STONE))
         } else if scores[&WHITE_STONE] > scores[&BLACK_STONE] {
@@ -397,7 +1052,63 @@ impl Game {
         }
     }
 
+    // Result of counting the position as it stands, with the marked dead stones removed
+    pub fn scored_result(&self) -> GameResult {
+        let difference = self.calculate_scores_difference();
+        if difference > 0.0 {
+            GameResult::Score(BLACK_STONE, difference)
+        } else if difference < 0.0 {
+            GameResult::Score(WHITE_STONE, -difference)
+        } else {
+            GameResult::Draw
+        }
+    }
+
     fn calculate_scores(&self) -> HashMap<Stone, f32> {
+        if !self.dead_stones.is_empty() {
+            let mut position = self.clone();
+            position.remove_dead_stones();
+            return position.calculate_scores();
+        }
+        match self.rules.scoring {
+            Scoring::Area => self.calculate_area_scores(),
+            Scoring::Territory => self.calculate_territory_scores(),
+        }
+    }
+
+    // Takes the dead stones off the board as prisoners of their opponent
+    fn remove_dead_stones(&mut self) {
+        for coords in self.dead_stones.drain() {
+            if let Some(color) = self.board[coords] {
+                *self.prisoners.get_mut(&Some(!color)).unwrap() += 1;
+                self.board.set(coords, EMPTY);
+            }
+        }
+        self.chains = Chains::from_board(&self.board);
+    }
+
+    // Owner of every point, by stones or by surrounded empty regions
+    pub fn ownership(&self) -> Vec<Vec<Stone>> {
+        if !self.dead_stones.is_empty() {
+            let mut position = self.clone();
+            position.remove_dead_stones();
+            return position.ownership();
+        }
+        let mut ownership = self.board.rows().clone();
+        let seki_points = self.seki_points();
+        let seki_stones = self.seki_stones(&seki_points);
+        for region in self.regions() {
+            let owner = self
+                .territory_owner(&region, &seki_points, &seki_stones)
+                .map_or(EMPTY, |(player, _)| player.get_stone());
+            for (x, y) in region.points {
+                ownership[x][y] = owner;
+            }
+        }
+        ownership
+    }
+
+    fn calculate_area_scores(&self) -> HashMap<Stone, f32> {
         /*
         Area Scoring
 
@@ -416,29 +1127,56 @@ impl Game {
         White's score is 70 + 45 = 115; black's score is 60 + 35 = 95; the margin of victory is 20 points to white.
         */
         let mut scores: HashMap<Stone, f32> = HashMap::from([
-            (BLACK_STONE, self.number_stones(BLACK_STONE) as f32),
-            (WHITE_STONE, self.komi + self.number_stones(WHITE_STONE) as f32),
+            (BLACK_STONE, self.number_stones(BLACK_STONE)),
+            (WHITE_STONE, self.rules.komi + self.handicap_compensation() + self.number_stones(WHITE_STONE)),
         ]);
 
-        for (i, row) in self.board.data.iter().enumerate() {
-            for (j, cell) in row.iter().enumerate() {
-                if cell.is_none() {
-                    match self.territory_owner((i,j)) {
-                        Some((player, count)) => {
-                            let player_stone = player.get_stone();
-                            let current_score = scores[&player_stone];
-                            scores.insert(player_stone, current_score + count as f32);
-                        }
-                        None => {
-                            continue
-                        }
-                    }
-                }
+        let seki_points = self.seki_points();
+        let seki_stones = self.seki_stones(&seki_points);
+        for region in self.regions() {
+            if let Some((player, count)) = self.territory_owner(&region, &seki_points, &seki_stones) {
+                let player_stone = player.get_stone();
+                let current_score = scores[&player_stone];
+                scores.insert(player_stone, current_score + count as f32);
             }
         }
         scores
     }
 
+    fn calculate_territory_scores(&self) -> HashMap<Stone, f32> {
+        /*
+        Territory Scoring
+
+        In territory scoring, your score is:
+
+        - The number of empty points which only your stones surround
+
+        - The number of stones you captured (prisoners)
+
+        Territory scoring is used in Japanese and Korean rules.
+        */
+        let mut scores: HashMap<Stone, f32> = HashMap::from([
+            (BLACK_STONE, self.prisoners[&BLACK_STONE] as f32),
+            (WHITE_STONE, self.rules.komi + self.handicap_compensation() + self.prisoners[&WHITE_STONE] as f32),
+        ]);
+
+        let seki_points = self.seki_points();
+        let seki_stones = self.seki_stones(&seki_points);
+        for region in self.regions() {
+            if let Some((player, count)) = self.territory_owner(&region, &seki_points, &seki_stones) {
+                let player_stone = player.get_stone();
+                let current_score = scores[&player_stone];
+                scores.insert(player_stone, current_score + count as f32);
+            }
+        }
+        scores
+    }
+
+    // Points given to White for the handicap stones, depending on the rules
+    fn handicap_compensation(&self) -> f32 {
+        self.rules.handicap_compensation.points(self.handicap)
+    }
+
     pub fn scores_difference(&self, scores: HashMap<Stone, f32>) -> f32 {
         scores[&BLACK_STONE] - scores[&WHITE_STONE]
     }
@@ -448,29 +1186,103 @@ impl Game {
         scores[&BLACK_STONE] - scores[&WHITE_STONE]
     }
 
-    fn territory_owner(&self, coords: Coordinates) -> Option<(Rc<Box<dyn Player>>, usize)> {
-        if self.board[coords] != EMPTY {
-            panic!("Cannot call territory_owner on an empty case")
+    /*
+        Player owning the region as territory, with its size.
+        Regions holding shared liberties of a seki belong to nobody. Under territory scoring the eyes
+        of chains in seki, regions surrounded by nothing but those chains, are not counted either.
+    */
+    fn territory_owner(
+        &self,
+        region: &Region,
+        seki_points: &[Coordinates],
+        seki_stones: &HashSet<Coordinates>,
+    ) -> Option<(Rc<Box<dyn Player>>, usize)> {
+        if region.points.iter().any(|point| seki_points.contains(point)) {
+            return None;
         }
+        if self.rules.scoring == Scoring::Territory && region.border.iter().all(|stone| seki_stones.contains(stone)) {
+            return None;
+        }
+        region
+            .owner()
+            .map(|owner| (self.find_player(owner), region.size()))
+    }
 
-        let group_dict = Self::flood_fill(coords, &self.board, true, false);
-        let border = group_dict.get("border").unwrap();
-        if border.values().all(|neighbor| *neighbor == BLACK_STONE) {
-            Some((
-                Rc::clone(&self.find_player(BLACK_STONE)),
-                group_dict.get("group").iter().len(),
-            ))
-        } else if border.values().all(|neighbor| *neighbor == WHITE_STONE) {
-            Some((
-                Rc::clone(&self.find_player(WHITE_STONE)),
-                group_dict.get("group").iter().len(),
-            ))
+    /*
+        Empty points next to chains of both players, that neither player can fill without putting itself in atari,
+        and whose chains cannot be approached: the opponent cannot fill any of their liberties without putting
+        itself in atari either. Otherwise one side wins the capturing race and the point is not seki
+    */
+    pub fn seki_points(&self) -> Vec<Coordinates> {
+        if !self.dead_stones.is_empty() {
+            let mut position = self.clone();
+            position.remove_dead_stones();
+            return position.seki_points();
+        }
+        let (height, width) = self.board.shape;
+        let mut points: Vec<Coordinates> = vec![];
+        for x in 0..height {
+            for y in 0..width {
+                let is_seki_point = self.board[(x, y)] == EMPTY
+                    && [BLACK_STONE, WHITE_STONE].into_iter().all(|stone| {
+                        !self.chains.adjacent_chains((x, y), stone).is_empty()
+                            && !self.is_safe_move((x, y), stone)
+                    })
+                    && self.board.neighbors((x, y)).into_iter().all(|neighbor| !self.can_be_approached(neighbor));
+                if is_seki_point {
+                    points.push((x, y));
+                }
+            }
+        }
+        points
+    }
+
+    // Whether stone can be played at the empty point coords without ending up in atari, or captures
+    fn is_safe_move(&self, coords: Coordinates, stone: Stone) -> bool {
+        self.chains.liberties_after(coords, stone) > 1 || !self.chains.captured_by(coords, stone).is_empty()
+    }
+
+    // Whether the opponent of the chain at coords can safely fill one of its liberties, false on an empty point
+    fn can_be_approached(&self, coords: Coordinates) -> bool {
+        let Some(color) = self.board[coords] else {
+            return false;
+        };
+        self.chains
+            .liberties(coords)
+            .into_iter()
+            .any(|liberty| self.is_safe_move(liberty, Some(!color)))
+    }
+
+    // Stones of the living chains touching a seki point
+    fn seki_stones(&self, seki_points: &[Coordinates]) -> HashSet<Coordinates> {
+        let mut stones: HashSet<Coordinates> = HashSet::new();
+        for point in seki_points {
+            for neighbor in self.board.neighbors(*point) {
+                if self.board[neighbor].is_some() && !self.dead_stones.contains(&neighbor) {
+                    stones.extend(self.chains.stones(neighbor));
+                }
+            }
+        }
+        stones
+    }
+
+    pub fn is_in_seki(&self, coords: Coordinates) -> bool {
+        self.seki_stones(&self.seki_points()).contains(&coords)
+    }
+
+    // Status of the chain at coords given the marked dead stones, None on an empty point
+    pub fn chain_status(&self, coords: Coordinates) -> Option<ChainStatus> {
+        self.board[coords]?;
+        if self.dead_stones.contains(&coords) {
+            Some(ChainStatus::Dead)
+        } else if self.is_in_seki(coords) {
+            Some(ChainStatus::Seki)
         } else {
-            None
+            Some(ChainStatus::Alive)
         }
     }
 
-    fn eye_owner(&self, coords: Coordinates) -> Option<Stone> {
+    pub fn eye_owner(&self, coords: Coordinates) -> Option<Stone> {
         if self.board[coords].is_some(){
             panic!("Did not call eye_owner on an empty case")
         }
@@ -479,105 +1291,398 @@ impl Game {
             .into_iter()
             .map(|neighbor_coord| self.board[neighbor_coord])
             .collect();
-        let mut neighbors_iter = neighbors.iter();
-        if neighbors_iter.all(|neighbor| *neighbor == BLACK_STONE) {
+        if neighbors.iter().all(|neighbor| *neighbor == BLACK_STONE) {
             Some(BLACK_STONE)
-        } else if neighbors_iter.all(|neighbor| *neighbor == WHITE_STONE) {
+        } else if neighbors.iter().all(|neighbor| *neighbor == WHITE_STONE) {
             Some(WHITE_STONE)
         } else {
             None
         }
     }
 
-    fn is_eye(&self, coords: Coordinates) -> bool {
+    pub fn is_eye(&self, coords: Coordinates) -> bool {
         self.eye_owner(coords).is_some()
     }
 
     fn group_owner(&self, coords: Coordinates) -> Option<Rc<Box<dyn Player>>> {
-        let group_dict = Self::flood_fill(coords, &self.board, false, true);
-        let group = &group_dict["group"];
-        let mut n_eyes: u8 = 0;
-        let group_tuples = group.into_iter().map(|coord_and_stone| coord_and_stone);
-        for (coord, stone) in group_tuples {
-            let is_eye = self.eye_owner(*coord);
-            if *stone == EMPTY && is_eye.is_some() {
-                n_eyes += 1;
-                if n_eyes >= 2 {
-                    let stone = is_eye.unwrap();
-                    let player = self.find_player(stone);
-                    return Some(player.clone());
-                }
-            }
+        let chain = self.chain_at(coords)?;
+        let n_eyes = chain
+            .liberties
+            .iter()
+            .filter(|liberty| self.eye_owner(**liberty) == Some(chain.color))
+            .count();
+        if n_eyes >= 2 {
+            Some(self.find_player(chain.color))
+        } else {
+            None
         }
-        None
     }
 
-    fn is_alive(&self, coords: Coordinates) -> bool {
+    pub fn is_alive(&self, coords: Coordinates) -> bool {
         self.group_owner(coords).is_some()
     }
 
-    fn number_stones(&self, _s: Stone) -> f32 {
+    fn number_stones(&self, s: Stone) -> f32 {
         let mut res = 0.0;
-        for row in &self.board.data {
+        for row in self.board.rows() {
             for stone in row {
-                match stone {
-                    _s => res += 1.0,
+                if *stone == s {
+                    res += 1.0;
                 }
             }
         }
         res
     }
 
-    pub fn available_cases(&self, player: Rc<Box<dyn Player>>) -> Vec<Move> {
+    pub fn available_cases(&self) -> Vec<Move> {
         let mut available_cases: Vec<Move> = vec![];
-        for i in 0..self.board_size {
-            for j in 0..self.board_size {
+        let (height, width) = self.board.shape;
+        for i in 0..height {
+            for j in 0..width {
                 if self.board[(i,j)].is_none() {
                     let induces_suicide = self.induces_suicide((i,j));
-                    if !induces_suicide {
-                        available_cases.push(Some((i,j)))
+                    if !induces_suicide || self.rules.suicide_allowed {
+                        available_cases.push(Move::Play((i,j)))
                     }
                 }
             }
         }
-        available_cases.push(None);
+        available_cases.push(Move::Pass);
         available_cases
     }
 }
 
 impl Display for Game {
-    fn fmt(&self, _f: &mut Formatter<'_>) -> Result<(), Error> {
-        println!("It's {} player turn\n", self.get_current_player());
-        // Print each row with border
-        for row in self.board.data.iter() {
-            // Print each cell in the row
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        writeln!(f, "It's {} player turn\n", self.get_current_player())?;
+        let (height, width) = self.board.shape;
+        // row numbers are right aligned, and column letters are above and below the points
+        let label_width = height.to_string().len();
+        let columns: String = (0..width).map(|y| format!(" {}", Point::column_letter(y))).collect();
+        writeln!(f, "{:label_width$} {}", "", columns)?;
+        for (x, row) in self.board.rows().iter().enumerate() {
+            write!(f, "{:>label_width$} ", height - x)?;
             for cell in row {
                 let c = match *cell {
                     WHITE_STONE => WHITE_STONE_CHAR,
                     BLACK_STONE => BLACK_STONE_CHAR,
                     EMPTY => EMPTY_CHAR,
-                    _ => panic!("Unknown value found"),
                 };
-                print!("|{}", c);
+                write!(f, "|{}", c)?;
             }
-            println!("|");
+            writeln!(f, "| {}", height - x)?;
         }
+        writeln!(f, "{:label_width$} {}", "", columns)?;
         Ok(())
     }
 }
 
 impl Default for Game {
     fn default() -> Self {
-        Game {
-            board_size:SIDE,
-            board:Board::default(),
-            last_boards: [Board::default(), Board::default()],
+        let board = Board::default();
+        let position_history = vec![(board.position_hash(), BLACK_STONE)];
+        let mut game = Game {
+            chains: Chains::from_board(&board),
+            board,
+            position_history,
+            rules: Rules::default(),
+            handicap: 0,
+            prisoners: HashMap::from([(BLACK_STONE, 0), (WHITE_STONE, 0)]),
+            dead_stones: HashSet::new(),
             players: [Rc::new(Box::new(Human::new(BLACK_STONE))), Rc::new(Box::new(Human::new(WHITE_STONE)))],
             current_player: 0,
             display: true,
             last_turned_passed: false,
-            komi:7.5,
             is_over: false,
+            result: None,
+            start: StartPosition::default(),
+            records: vec![],
+            setups: vec![],
+            undone_records: vec![],
+        };
+        game.mark_start();
+        game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::KoRule;
+    use crate::test_support::{players, position};
+
+    fn game_with_ko_rule(shape: BoardSize, ko_rule: KoRule) -> Game {
+        Game::new(players(), shape, false, Rules { ko_rule, ..Rules::default() }).unwrap()
+    }
+
+    /*
+        Kos side by side on a 3 rows board, every 5 columns. In ko k, Black takes by playing at (1, 5k + 2)
+        and White by playing at (1, 5k + 1). The position starts with the kos in the given states,
+        true when Black took last, and Black to play
+    */
+    fn kos_position(black_took: &[bool], ko_rule: KoRule) -> Game {
+        let mut game = game_with_ko_rule((3, 5 * black_took.len() - 1), ko_rule);
+        let mut stones: Vec<(Coordinates, Stone)> = vec![];
+        for (k, black_took) in black_took.iter().enumerate() {
+            let c = 5 * k;
+            stones.extend([((0, c + 1), BLACK_STONE), ((2, c + 1), BLACK_STONE), ((1, c), BLACK_STONE)]);
+            stones.extend([((0, c + 2), WHITE_STONE), ((2, c + 2), WHITE_STONE), ((1, c + 3), WHITE_STONE)]);
+            stones.push(if *black_took { ((1, c + 2), BLACK_STONE) } else { ((1, c + 1), WHITE_STONE) });
+        }
+        game.set_stones(&stones, BLACK_STONE).unwrap();
+        game
+    }
+
+    fn black_takes(k: usize) -> Move {
+        Move::Play((1, 5 * k + 2))
+    }
+
+    fn white_takes(k: usize) -> Move {
+        Move::Play((1, 5 * k + 1))
+    }
+
+    #[test]
+    fn ko_cannot_be_retaken_at_once() {
+        for ko_rule in [KoRule::Simple, KoRule::PositionalSuperko, KoRule::SituationalSuperko] {
+            let mut game = kos_position(&[false], ko_rule);
+            assert_eq!(game.step(black_takes(0)), Ok(()));
+            assert_eq!(game.prisoners(BLACK_STONE), 1);
+            let expected = if ko_rule == KoRule::Simple { Signals::BreakingKo } else { Signals::BreakingSuperko };
+            assert_eq!(game.step(white_takes(0)), Err(expected));
+            // after a move elsewhere the ko can be taken back
+            assert_eq!(game.step(Move::Play((0, 3))), Ok(()));
+            assert_eq!(game.step(Move::Pass), Ok(()));
+            assert_eq!(game.step(white_takes(0)), Ok(()));
+        }
+    }
+
+    // Each player takes a ko in turn, the sixth move bringing back the starting position
+    fn triple_ko_cycle(ko_rule: KoRule) -> Result<(), Signals> {
+        let mut game = kos_position(&[false, true, false], ko_rule);
+        for played_move in [black_takes(0), white_takes(1), black_takes(2), white_takes(0), black_takes(1)] {
+            assert_eq!(game.step(played_move), Ok(()));
+        }
+        game.step(white_takes(2))
+    }
+
+    #[test]
+    fn triple_ko_is_forbidden_by_superko_only() {
+        assert_eq!(triple_ko_cycle(KoRule::Simple), Ok(()));
+        assert_eq!(triple_ko_cycle(KoRule::PositionalSuperko), Err(Signals::BreakingSuperko));
+        assert_eq!(triple_ko_cycle(KoRule::SituationalSuperko), Err(Signals::BreakingSuperko));
+    }
+
+    // The starting board comes back with White to play instead of Black thanks to a pass
+    fn repetition_with_other_player(ko_rule: KoRule) -> Result<(), Signals> {
+        let mut game = kos_position(&[false, true, false], ko_rule);
+        for played_move in [black_takes(0), white_takes(1), black_takes(2), white_takes(0), Move::Pass, white_takes(2)] {
+            assert_eq!(game.step(played_move), Ok(()));
+        }
+        game.step(black_takes(1))
+    }
+
+    #[test]
+    fn situational_superko_allows_the_position_with_the_other_player_to_move() {
+        assert_eq!(repetition_with_other_player(KoRule::PositionalSuperko), Err(Signals::BreakingSuperko));
+        assert_eq!(repetition_with_other_player(KoRule::SituationalSuperko), Ok(()));
+    }
+
+    // Player who considers the stone at (0, 0) dead, and accepts or disputes every proposal of its opponent
+    struct DeadStonesJudge {
+        stone: Stone,
+        accepts: bool,
+    }
+
+    impl Player for DeadStonesJudge {
+        fn get_stone(&self) -> Stone {
+            self.stone
+        }
+        fn choose_case(&self, _game: &Game) -> Move {
+            Move::Pass
+        }
+        fn propose_dead_stones(&self, _game: &Game) -> Vec<Coordinates> {
+            vec![(0, 0)]
+        }
+        fn review_dead_stones(&self, game: &Game, _proposal: &[Coordinates]) -> DeadStonesResponse {
+            if self.accepts {
+                DeadStonesResponse::Accept
+            } else {
+                DeadStonesResponse::Dispute(self.propose_dead_stones(game))
+            }
+        }
+    }
+
+    fn game_after_double_pass(accepts: bool) -> Game {
+        let players: [Rc<Box<dyn Player>>; 2] = [
+            Rc::new(Box::new(DeadStonesJudge { stone: BLACK_STONE, accepts })),
+            Rc::new(Box::new(DeadStonesJudge { stone: WHITE_STONE, accepts })),
+        ];
+        let mut game = Game::new(players, (5, 5), false, Rules::default()).unwrap();
+        game.set_stones(&[((0, 0), BLACK_STONE), ((4, 4), WHITE_STONE)], BLACK_STONE).unwrap();
+        assert_eq!(game.step(Move::Pass), Ok(()));
+        assert_eq!(game.step(Move::Pass), Err(Signals::DoublePass));
+        game
+    }
+
+    #[test]
+    fn agreed_dead_stones_end_the_game() {
+        let mut game = game_after_double_pass(true);
+        assert!(game.end_play());
+        assert!(game.is_over());
+        assert!(game.dead_stones().contains(&(0, 0)));
+    }
+
+    #[test]
+    fn disputed_dead_stones_resume_play() {
+        let mut game = game_after_double_pass(false);
+        assert!(!game.end_play());
+        assert!(!game.is_over());
+        assert!(game.dead_stones().is_empty());
+        assert_eq!(game.step(Move::Play((2, 2))), Ok(()));
+    }
+
+    #[test]
+    fn chains_sharing_their_last_liberties_are_in_seki() {
+        let rows = [
+            ".X.OX..",
+            "OOOOX..",
+            "XXXXX..",
+            ".......",
+            ".......",
+            ".......",
+            ".......",
+        ];
+        for rules in [Rules::chinese(), Rules::japanese()] {
+            let game = position(&rows, rules);
+            assert_eq!(game.seki_points(), vec![(0, 0), (0, 2)]);
+            assert_eq!(game.chain_status((0, 1)), Some(ChainStatus::Seki));
+            assert_eq!(game.chain_status((1, 0)), Some(ChainStatus::Seki));
+            assert_eq!(game.chain_status((2, 0)), Some(ChainStatus::Alive));
+            assert_eq!(game.ownership()[0][0], EMPTY);
+            assert_eq!(game.ownership()[3][3], BLACK_STONE);
+        }
+    }
+
+    #[test]
+    fn capturing_race_won_by_approaching_is_not_seki() {
+        /*
+            Neither player can fill (0, 2) without putting itself in atari, but Black can safely fill
+            the outside liberty of White at (2, 3) first and then capture
+        */
+        let game = position(
+            &[
+                ".X.OX..",
+                "XXOOX..",
+                "OOO.X..",
+                "XXXXX..",
+                ".......",
+                ".......",
+                ".......",
+            ],
+            Rules::default(),
+        );
+        assert!(game.seki_points().is_empty());
+        assert_eq!(game.chain_status((0, 1)), Some(ChainStatus::Alive));
+    }
+
+    #[test]
+    fn undo_and_redo_a_capture() {
+        let mut game = game_with_ko_rule((5, 5), KoRule::Simple);
+        for played_move in [Move::Play((0, 1)), Move::Play((0, 0))] {
+            assert_eq!(game.step(played_move), Ok(()));
+        }
+        let hash_before_capture = game.position_hash();
+        assert_eq!(game.step(Move::Play((1, 0))), Ok(()));
+        let hash_after_capture = game.position_hash();
+        assert_eq!(game.prisoners(BLACK_STONE), 1);
+
+        assert_eq!(game.undo(), Some(Move::Play((1, 0))));
+        assert_eq!(game.position_hash(), hash_before_capture);
+        assert_eq!(game.prisoners(BLACK_STONE), 0);
+        assert_eq!(game.get_board()[(0, 0)], WHITE_STONE);
+        assert_eq!(game.get_current_player().get_stone(), BLACK_STONE);
+
+        assert_eq!(game.redo(), Some(Move::Play((1, 0))));
+        assert_eq!(game.position_hash(), hash_after_capture);
+        assert_eq!(game.prisoners(BLACK_STONE), 1);
+        assert_eq!(game.get_board()[(0, 0)], EMPTY);
+        assert_eq!(game.redo(), None);
+    }
+
+    #[test]
+    fn undo_stops_at_the_handicap_stones() {
+        let mut game = game_with_ko_rule((9, 9), KoRule::Simple);
+        let handicap_stones = game.set_fixed_handicap(2).unwrap();
+        let start_hash = game.position_hash();
+        for played_move in [Move::Play((4, 4)), Move::Play((4, 5))] {
+            assert_eq!(game.step(played_move), Ok(()));
+        }
+        assert_eq!(game.undo(), Some(Move::Play((4, 5))));
+        assert_eq!(game.undo(), Some(Move::Play((4, 4))));
+        assert_eq!(game.undo(), None);
+        assert_eq!(game.position_hash(), start_hash);
+        assert_eq!(game.get_current_player().get_stone(), WHITE_STONE);
+        for coords in handicap_stones {
+            assert_eq!(game.get_board()[coords], BLACK_STONE);
+        }
+        assert_eq!(game.redo(), Some(Move::Play((4, 4))));
+        assert_eq!(game.get_board()[(4, 4)], WHITE_STONE);
+    }
+
+    // White stones around the corner, where Black at (0, 0) then (0, 1) kills its own two stones
+    fn suicide_position(ko_rule: KoRule) -> Game {
+        let rules = Rules { suicide_allowed: true, ko_rule, ..Rules::default() };
+        position(&["..O..", "OO...", ".....", ".....", "....."], rules)
+    }
+
+    #[test]
+    fn multi_stone_suicide_removes_the_stones() {
+        let mut game = suicide_position(KoRule::SituationalSuperko);
+        let start_hash = game.get_board().position_hash();
+        for played_move in [Move::Play((0, 0)), Move::Play((4, 4))] {
+            assert_eq!(game.step(played_move), Ok(()));
+        }
+        let hash_before_suicide = game.get_board().position_hash();
+        assert_eq!(game.step(Move::Play((0, 1))), Ok(()));
+        assert_eq!(game.get_board()[(0, 0)], EMPTY);
+        assert_eq!(game.get_board()[(0, 1)], EMPTY);
+        assert_eq!(game.prisoners(WHITE_STONE), 2);
+        assert_eq!(game.last_record().unwrap().captured.len(), 2);
+        let board_hash = game.get_board().position_hash();
+        assert_eq!(board_hash, start_hash ^ zobrist::stone_key((4, 4), WHITE_STONE));
+        assert_ne!(board_hash, hash_before_suicide);
+        assert_eq!(game.get_current_player().get_stone(), WHITE_STONE);
+    }
+
+    #[test]
+    fn single_stone_suicide_is_refused_even_when_suicide_is_allowed() {
+        let rows = [".O...", "O....", ".....", ".....", "....."];
+        for suicide_allowed in [true, false] {
+            let mut game = position(&rows, Rules { suicide_allowed, ..Rules::default() });
+            let start_hash = game.position_hash();
+            assert_eq!(game.step(Move::Play((0, 0))), Err(Signals::InducesSuicide));
+            assert_eq!(game.prisoners(WHITE_STONE), 0);
+            assert_eq!(game.position_hash(), start_hash);
+            assert_eq!(game.get_current_player().get_stone(), BLACK_STONE);
+        }
+    }
+
+    #[test]
+    fn suicide_repeating_a_position_follows_the_ko_rule() {
+        // after a white pass, the suicide brings back the start position with White to play instead of Black
+        for (ko_rule, expected) in [
+            (KoRule::PositionalSuperko, Err(Signals::BreakingSuperko)),
+            (KoRule::SituationalSuperko, Ok(())),
+        ] {
+            let mut game = suicide_position(ko_rule);
+            let start_hash = game.get_board().position_hash();
+            for played_move in [Move::Play((0, 0)), Move::Pass] {
+                assert_eq!(game.step(played_move), Ok(()));
+            }
+            assert_eq!(game.step(Move::Play((0, 1))), expected);
+            if expected.is_ok() {
+                assert_eq!(game.get_board().position_hash(), start_hash);
+            }
         }
     }
 }
@@ -0,0 +1,958 @@
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::board::{BoardSize, DEFAULT_SIDE};
+use crate::game::{Coordinates, Game, Move};
+use crate::life::{self, ChainStatus};
+use crate::mcts::tree::{MoveStatistics, Tree};
+use crate::players::bot::Bot;
+use crate::players::player::Player;
+use crate::point::Point;
+use crate::rules::Rules;
+use crate::signals::Signals;
+use crate::stones::{Stone, BLACK_STONE, WHITE_STONE};
+
+pub const PROTOCOL_VERSION: &str = "2";
+pub const ENGINE_NAME: &str = "gobot-rs";
+
+pub const COMMANDS: &[&str] = &[
+    "protocol_version",
+    "name",
+    "version",
+    "known_command",
+    "list_commands",
+    "quit",
+    "boardsize",
+    "clear_board",
+    "komi",
+    "play",
+    "genmove",
+    "undo",
+    "showboard",
+    "final_score",
+    "final_status_list",
+    "fixed_handicap",
+    "place_free_handicap",
+    "set_free_handicap",
+    "time_settings",
+    "time_left",
+    "gogui-analyze_commands",
+    "gobot-visits",
+    "gobot-winrates",
+    "gobot-pv",
+    "gobot-ownership",
+    "gobot-legal_moves",
+    "lz-analyze",
+    "kata-analyze",
+];
+
+// Analyze commands shown by GoGui, as type/label/command
+const ANALYZE_COMMANDS: &[&str] = &[
+    "sboard/Visits/gobot-visits",
+    "sboard/Win Rates/gobot-winrates",
+    "var/Principal Variation/gobot-pv",
+    "dboard/Ownership/gobot-ownership",
+    "plist/Legal Moves/gobot-legal_moves",
+];
+// Search iterations added to the analysis tree by every analyze command, so that repeated calls refine the results
+const ANALYSIS_ITERATIONS: u32 = 200;
+// Search iterations run between two checks for a new command while streaming an analysis
+const ANALYSIS_BATCH: u32 = 10;
+// Time between two analysis reports when the controller does not give any
+const DEFAULT_ANALYSIS_INTERVAL: Duration = Duration::from_secs(1);
+
+// Output format of the live analysis commands
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AnalysisFormat {
+    /// lz-analyze of Leela Zero, win rates in hundredths of percent
+    Leela,
+    /// kata-analyze of KataGo, win rates between 0 and 1 along with score leads
+    KataGo,
+}
+
+impl AnalysisFormat {
+    fn from_command(command: &str) -> Option<AnalysisFormat> {
+        match command {
+            "lz-analyze" => Some(AnalysisFormat::Leela),
+            "kata-analyze" => Some(AnalysisFormat::KataGo),
+            _ => None,
+        }
+    }
+}
+
+struct AnalysisRequest {
+    format: AnalysisFormat,
+    // player the position is analysed for
+    stone: Stone,
+    interval: Duration,
+    max_moves: Option<usize>,
+    // moves to report even when the search did not reach that many yet
+    min_moves: usize,
+    // whether kata-analyze reports end with the ownership of every point
+    ownership: bool,
+}
+
+// Vertex like D4, columns from the left without I and rows from the bottom, or pass
+pub fn parse_vertex(vertex: &str, shape: BoardSize) -> Option<Move> {
+    if vertex.eq_ignore_ascii_case("pass") {
+        return Some(Move::Pass);
+    }
+    Point::from_notation(vertex, shape).map(|point| Move::Play(point.into()))
+}
+
+pub fn vertex(played_move: Move, shape: BoardSize) -> String {
+    match played_move {
+        Move::Play(coords) => Point::from(coords).notation(shape),
+        Move::Pass => "pass".to_string(),
+        Move::Resign => "resign".to_string(),
+    }
+}
+
+fn parse_color(color: &str) -> Option<Stone> {
+    match color.to_lowercase().as_str() {
+        "b" | "black" => Some(BLACK_STONE),
+        "w" | "white" => Some(WHITE_STONE),
+        _ => None,
+    }
+}
+
+// Removes comments and control characters, and turns tabs into spaces
+fn preprocess(line: &str) -> String {
+    let line = line.split('#').next().unwrap_or("");
+    line.chars()
+        .filter_map(|c| match c {
+            '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/*
+    Go Text Protocol version 2 front end, playing with a Bot for each color.
+    GTP lets the controller play several moves of the same color in a row: the engine then inserts
+    a pass of the other color, which is taken back along with the move on undo
+*/
+pub struct GtpEngine {
+    game: Game,
+    players: [Rc<Box<dyn Player>>; 2],
+    shape: BoardSize,
+    rules: Rules,
+    // move numbers of the passes inserted by the engine
+    inserted_passes: Vec<usize>,
+    // search tree of the analyze commands, kept while the position does not change
+    analysis: Tree,
+}
+
+impl GtpEngine {
+    pub fn new() -> GtpEngine {
+        let players: [Rc<Box<dyn Player>>; 2] = [
+            Rc::new(Box::new(Bot::new(BLACK_STONE))),
+            Rc::new(Box::new(Bot::new(WHITE_STONE))),
+        ];
+        let shape = (DEFAULT_SIDE, DEFAULT_SIDE);
+        let rules = Rules::default();
+        GtpEngine {
+            game: Game::new(players.clone(), shape, false, rules).expect("The default board size is valid"),
+            players,
+            shape,
+            rules,
+            inserted_passes: vec![],
+            analysis: Tree::new(),
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /*
+        Reads commands until quit or the end of the input, answering each of them on the output.
+        The input is read on its own thread so that the next command can stop a live analysis
+    */
+    pub fn run(&mut self, input: impl BufRead + Send + 'static, mut output: impl Write) -> io::Result<()> {
+        let (sender, commands) = mpsc::channel::<io::Result<String>>();
+        thread::spawn(move || {
+            for line in input.lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut next_line: Option<String> = None;
+        loop {
+            let line = match next_line.take() {
+                Some(line) => line,
+                None => match commands.recv() {
+                    Ok(line) => line?,
+                    Err(_) => break,
+                },
+            };
+            let line = preprocess(&line);
+            let mut words = line.split_whitespace().peekable();
+            let id = words.next_if(|word| word.parse::<u32>().is_ok()).unwrap_or("");
+            let Some(command) = words.next() else {
+                continue;
+            };
+            let arguments: Vec<&str> = words.collect();
+            if let Some(format) = AnalysisFormat::from_command(command) {
+                match self.parse_analysis(format, &arguments) {
+                    Ok(request) => {
+                        writeln!(output, "={}", id)?;
+                        match self.stream_analysis(&request, &commands, &mut output)? {
+                            Some(line) => next_line = Some(line),
+                            None => break,
+                        }
+                    }
+                    Err(error) => {
+                        write!(output, "?{} {}\n\n", id, error)?;
+                        output.flush()?;
+                    }
+                }
+                continue;
+            }
+            match self.execute(command, &arguments) {
+                Ok(response) => write!(output, "={} {}\n\n", id, response.trim_end())?,
+                Err(error) => write!(output, "?{} {}\n\n", id, error)?,
+            }
+            output.flush()?;
+            if command == "quit" {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn execute(&mut self, command: &str, arguments: &[&str]) -> Result<String, String> {
+        match (command, arguments) {
+            ("protocol_version", _) => Ok(PROTOCOL_VERSION.to_string()),
+            ("name", _) => Ok(ENGINE_NAME.to_string()),
+            ("version", _) => Ok(env!("CARGO_PKG_VERSION").to_string()),
+            ("known_command", [name]) => Ok(COMMANDS.contains(name).to_string()),
+            ("list_commands", _) => Ok(COMMANDS.join("\n")),
+            ("quit", _) => Ok(String::new()),
+            ("boardsize", [size]) => self.boardsize(size),
+            ("clear_board", _) => {
+                self.clear_board();
+                Ok(String::new())
+            }
+            ("komi", [komi]) => {
+                let komi = komi.parse::<f32>().map_err(|_| "syntax error".to_string())?;
+                self.rules.komi = komi;
+                self.game.set_komi(komi);
+                Ok(String::new())
+            }
+            ("play", [color, vertex]) => self.play(color, vertex),
+            ("genmove", [color]) => self.genmove(color),
+            ("undo", _) => self.undo(),
+            ("showboard", _) => Ok(self.showboard()),
+            ("final_score", _) => Ok(self.final_score()),
+            ("final_status_list", [status]) => self.final_status_list(status),
+            ("fixed_handicap", [handicap]) => self.fixed_handicap(handicap),
+            ("place_free_handicap", [handicap]) => self.place_free_handicap(handicap),
+            ("set_free_handicap", vertices) => self.set_free_handicap(vertices),
+            ("time_settings", [main_time, byo_yomi_time, byo_yomi_stones]) => {
+                self.time_settings(main_time, byo_yomi_time, byo_yomi_stones)
+            }
+            ("time_left", [color, time, stones]) => self.time_left(color, time, stones),
+            ("gogui-analyze_commands", _) => Ok(ANALYZE_COMMANDS.join("\n")),
+            ("gobot-visits", _) => Ok(self.visits_board()),
+            ("gobot-winrates", _) => Ok(self.win_rates_board()),
+            ("gobot-pv", _) => Ok(self.principal_variation()),
+            ("gobot-ownership", _) => Ok(self.ownership_board()),
+            ("gobot-legal_moves", _) => Ok(self.legal_moves()),
+            ("lz-analyze", _) => self.analysis_snapshot(AnalysisFormat::Leela, arguments),
+            ("kata-analyze", _) => self.analysis_snapshot(AnalysisFormat::KataGo, arguments),
+            _ if COMMANDS.contains(&command) => Err("syntax error".to_string()),
+            _ => Err("unknown command".to_string()),
+        }
+    }
+
+    fn clear_board(&mut self) {
+        self.game = Game::new(self.players.clone(), self.shape, false, self.rules).expect("The board size was accepted");
+        self.inserted_passes.clear();
+    }
+
+    fn boardsize(&mut self, size: &str) -> Result<String, String> {
+        let side = size.parse::<usize>().map_err(|_| "syntax error".to_string())?;
+        self.game = Game::new(self.players.clone(), (side, side), false, self.rules)
+            .map_err(|_| "unacceptable size".to_string())?;
+        self.shape = (side, side);
+        self.inserted_passes.clear();
+        Ok(String::new())
+    }
+
+    // Gives the turn to the given color, resuming play after a double pass
+    fn prepare_turn(&mut self, stone: Stone) {
+        if self.game.is_over() {
+            self.game.resume_play();
+        }
+        if self.game.get_current_player().get_stone() != stone {
+            self.inserted_passes.push(self.game.move_number());
+            let _ = self.game.step(Move::Pass);
+            if self.game.is_over() {
+                self.game.resume_play();
+            }
+        }
+    }
+
+    fn last_move_is_inserted_pass(&self) -> bool {
+        self.inserted_passes.last().is_some_and(|pass| pass + 1 == self.game.move_number())
+    }
+
+    // The inserted pass must not stay when the move it was inserted for is refused
+    fn take_back_inserted_pass(&mut self) {
+        if self.last_move_is_inserted_pass() {
+            self.game.undo();
+            self.inserted_passes.pop();
+        }
+    }
+
+    fn play(&mut self, color: &str, vertex: &str) -> Result<String, String> {
+        let stone = parse_color(color).ok_or("syntax error")?;
+        let played_move = parse_vertex(vertex, self.shape).ok_or("syntax error")?;
+        self.prepare_turn(stone);
+        match self.game.step(played_move) {
+            Ok(()) | Err(Signals::DoublePass) => Ok(String::new()),
+            Err(_) => {
+                self.take_back_inserted_pass();
+                Err("illegal move".to_string())
+            }
+        }
+    }
+
+    fn genmove(&mut self, color: &str) -> Result<String, String> {
+        let stone = parse_color(color).ok_or("syntax error")?;
+        self.prepare_turn(stone);
+        let player = self.game.get_current_player();
+        let chosen_move = player.choose_case(&self.game);
+        match self.game.step(chosen_move) {
+            Ok(()) | Err(Signals::DoublePass) | Err(Signals::Resignation) => {
+                if let Some(evaluation) = player.last_evaluation() {
+                    self.game.set_last_evaluation(evaluation);
+                }
+            }
+            Err(e) => {
+                // the bot only picks moves of its search tree, which are all legal
+                self.take_back_inserted_pass();
+                return Err(format!("engine chose the illegal move {}: {}", vertex(chosen_move, self.shape), e));
+            }
+        }
+        Ok(vertex(chosen_move, self.shape))
+    }
+
+    fn undo(&mut self) -> Result<String, String> {
+        self.game.undo().ok_or("cannot undo")?;
+        while self.last_move_is_inserted_pass() {
+            self.game.undo();
+            self.inserted_passes.pop();
+        }
+        Ok(String::new())
+    }
+
+    // Board as displayed to players, without the empty lines that would end the response
+    fn showboard(&self) -> String {
+        let board = self.game.to_string();
+        let lines: Vec<&str> = board.lines().filter(|line| !line.is_empty()).collect();
+        format!("\n{}", lines.join("\n"))
+    }
+
+    fn estimated_position(&self) -> Game {
+        let mut position = self.game.clone();
+        position.mark_estimated_dead_stones(life::DEFAULT_PLAYOUTS);
+        position
+    }
+
+    fn final_score(&self) -> String {
+        self.estimated_position().scored_result().to_string()
+    }
+
+    // Stones with the given status, one chain per line
+    fn final_status_list(&self, status: &str) -> Result<String, String> {
+        let status = match status {
+            "alive" => ChainStatus::Alive,
+            "dead" => ChainStatus::Dead,
+            "seki" => ChainStatus::Seki,
+            _ => return Err("syntax error".to_string()),
+        };
+        let lines: Vec<String> = life::estimate_life_and_death(&self.game, life::DEFAULT_PLAYOUTS)
+            .into_iter()
+            .filter(|estimate| estimate.status == status)
+            .map(|estimate| self.vertex_list(&estimate.chain.stones))
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    fn vertex_list(&self, stones: &[Coordinates]) -> String {
+        stones
+            .iter()
+            .map(|coords| vertex(Move::Play(*coords), self.shape))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn parse_handicap(&self, handicap: &str) -> Result<usize, String> {
+        let handicap = handicap.parse::<usize>().map_err(|_| "syntax error".to_string())?;
+        if self.game.move_number() > 0 || self.game.get_board().rows().iter().flatten().any(|stone| stone.is_some()) {
+            return Err("board not empty".to_string());
+        }
+        Ok(handicap)
+    }
+
+    fn fixed_handicap(&mut self, handicap: &str) -> Result<String, String> {
+        let handicap = self.parse_handicap(handicap)?;
+        let stones = self
+            .game
+            .set_fixed_handicap(handicap)
+            .map_err(|_| "invalid number of stones".to_string())?;
+        Ok(self.vertex_list(&stones))
+    }
+
+    fn place_free_handicap(&mut self, handicap: &str) -> Result<String, String> {
+        let handicap = self.parse_handicap(handicap)?;
+        let black_player = self.game.find_player(BLACK_STONE);
+        let stones = black_player.choose_handicap_stones(&self.game, handicap);
+        if stones.len() != handicap {
+            return Err("invalid number of stones".to_string());
+        }
+        self.game
+            .set_free_handicap(&stones)
+            .map_err(|_| "invalid number of stones".to_string())?;
+        Ok(self.vertex_list(&stones))
+    }
+
+    fn set_free_handicap(&mut self, vertices: &[&str]) -> Result<String, String> {
+        self.parse_handicap(&vertices.len().to_string())?;
+        let mut stones: Vec<Coordinates> = vec![];
+        for vertex in vertices {
+            match parse_vertex(vertex, self.shape) {
+                Some(Move::Play(coords)) => stones.push(coords),
+                _ => return Err("bad vertex list".to_string()),
+            }
+        }
+        self.game.set_free_handicap(&stones).map_err(|_| "bad vertex list".to_string())?;
+        Ok(String::new())
+    }
+
+    fn time_settings(&mut self, main_time: &str, byo_yomi_time: &str, byo_yomi_stones: &str) -> Result<String, String> {
+        let parse_seconds = |seconds: &str| seconds.parse::<u64>().map(Duration::from_secs).map_err(|_| "syntax error");
+        let main_time = parse_seconds(main_time)?;
+        let byo_yomi_time = parse_seconds(byo_yomi_time)?;
+        let byo_yomi_stones = byo_yomi_stones.parse::<u32>().map_err(|_| "syntax error")?;
+        for player in &self.players {
+            player.set_time_settings(main_time, byo_yomi_time, byo_yomi_stones);
+        }
+        Ok(String::new())
+    }
+
+    fn time_left(&mut self, color: &str, time: &str, stones: &str) -> Result<String, String> {
+        let stone = parse_color(color).ok_or("syntax error")?;
+        let time = time.parse::<u64>().map_err(|_| "syntax error")?;
+        let stones = stones.parse::<u32>().map_err(|_| "syntax error")?;
+        self.game.find_player(stone).set_time_left(Duration::from_secs(time), stones);
+        Ok(String::new())
+    }
+}
+
+// Analyze commands
+impl GtpEngine {
+    // Root statistics of the analysis tree after searching some more from the current position
+    fn analyse(&mut self) -> Vec<MoveStatistics> {
+        self.analysis.update_root_after_move(&self.game);
+        if !self.game.is_over() {
+            self.analysis.search(ANALYSIS_ITERATIONS);
+        }
+        self.analysis.root_statistics()
+    }
+
+    // GoGui board of one value per point, top row first
+    fn board_response<T: Display>(&self, values: &[Vec<T>]) -> String {
+        values
+            .iter()
+            .map(|row| row.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(" "))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn statistics_board(&mut self, value: impl Fn(&MoveStatistics) -> String) -> String {
+        let (height, width) = self.shape;
+        let mut board = vec![vec!["\"\"".to_string(); width]; height];
+        for move_statistics in self.analyse() {
+            if let Move::Play((x, y)) = move_statistics.played_move {
+                board[x][y] = value(&move_statistics);
+            }
+        }
+        self.board_response(&board)
+    }
+
+    fn visits_board(&mut self) -> String {
+        self.statistics_board(|move_statistics| move_statistics.visits.to_string())
+    }
+
+    // Win rates in percent of the player to move, for the moves searched at least once
+    fn win_rates_board(&mut self) -> String {
+        self.statistics_board(|move_statistics| {
+            if move_statistics.visits > 0 {
+                format!("{:.0}", 100.0 * move_statistics.win_rate)
+            } else {
+                "\"\"".to_string()
+            }
+        })
+    }
+
+    fn principal_variation(&mut self) -> String {
+        self.analyse();
+        let mut stone = self.game.get_current_player().get_stone();
+        let mut variation: Vec<String> = vec![];
+        for played_move in self.analysis.principal_variation() {
+            variation.push(format!("{} {}", if stone == BLACK_STONE { "b" } else { "w" }, vertex(played_move, self.shape)));
+            stone = Some(!stone.unwrap());
+        }
+        variation.join(" ")
+    }
+
+    // Share of the playouts each point ends up owned by Black, minus the share owned by White
+    fn ownership_board(&self) -> String {
+        let ownership = life::playout_ownership(&self.game, life::DEFAULT_PLAYOUTS);
+        let board: Vec<Vec<String>> = ownership
+            .black
+            .iter()
+            .zip(ownership.white.iter())
+            .map(|(black_row, white_row)| {
+                black_row
+                    .iter()
+                    .zip(white_row.iter())
+                    .map(|(black, white)| format!("{:.2}", black - white))
+                    .collect()
+            })
+            .collect();
+        self.board_response(&board)
+    }
+
+    fn legal_moves(&self) -> String {
+        let points: Vec<Coordinates> = self
+            .game
+            .available_cases()
+            .into_iter()
+            .filter_map(|case| match case {
+                Move::Play(coords) => Some(coords),
+                _ => None,
+            })
+            .collect();
+        self.vertex_list(&points)
+    }
+}
+
+// Live analysis
+impl GtpEngine {
+    /*
+        Arguments of lz-analyze and kata-analyze: an optional color, then the interval in centiseconds
+        either alone or after the interval key. maxmoves, minmoves and ownership are followed, other keys are ignored
+    */
+    fn parse_analysis(&self, format: AnalysisFormat, arguments: &[&str]) -> Result<AnalysisRequest, String> {
+        let mut arguments = arguments.iter().peekable();
+        let stone = match arguments.peek().and_then(|color| parse_color(color)) {
+            Some(stone) => {
+                arguments.next();
+                stone
+            }
+            None => self.game.get_current_player().get_stone(),
+        };
+        let parse_number = |value: Option<&&str>| value.and_then(|value| value.parse::<u64>().ok()).ok_or("syntax error");
+        let mut interval = match arguments.next_if(|argument| argument.parse::<u64>().is_ok()) {
+            Some(centiseconds) => parse_number(Some(centiseconds))?,
+            None => 0,
+        };
+        let mut max_moves = None;
+        let mut min_moves = 0;
+        let mut ownership = false;
+        while let Some(key) = arguments.next() {
+            match *key {
+                "interval" => interval = parse_number(arguments.next())?,
+                "maxmoves" => max_moves = Some(parse_number(arguments.next())? as usize),
+                "minmoves" => min_moves = parse_number(arguments.next())? as usize,
+                "ownership" => {
+                    ownership = match arguments.next() {
+                        Some(&"true") => true,
+                        Some(&"false") => false,
+                        _ => return Err("syntax error".to_string()),
+                    }
+                }
+                // the color, moves and depth of the avoided or allowed moves
+                "avoid" | "allow" => {
+                    arguments.nth(2);
+                }
+                _ => {
+                    arguments.next();
+                }
+            }
+        }
+        let interval = match interval {
+            0 => DEFAULT_ANALYSIS_INTERVAL,
+            centiseconds => Duration::from_millis(10 * centiseconds),
+        };
+        Ok(AnalysisRequest { format, stone, interval, max_moves, min_moves, ownership })
+    }
+
+    // Current position with the given player to move, a pass being inserted for the other one if needed
+    fn analysis_position(&self, stone: Stone) -> Game {
+        let mut position = self.game.clone();
+        if position.is_over() {
+            position.resume_play();
+        }
+        if position.get_current_player().get_stone() != stone {
+            let _ = position.step(Move::Pass);
+            if position.is_over() {
+                position.resume_play();
+            }
+        }
+        position
+    }
+
+    /*
+        Searches until a new line arrives on the input, writing a report at every interval.
+        The response ends with an empty line, and the line that stopped it is given back
+        to be run next, None meaning the input was closed
+    */
+    fn stream_analysis(
+        &mut self,
+        request: &AnalysisRequest,
+        commands: &Receiver<io::Result<String>>,
+        output: &mut impl Write,
+    ) -> io::Result<Option<String>> {
+        output.flush()?;
+        let position = self.analysis_position(request.stone);
+        self.analysis.update_root_after_move(&position);
+        let mut last_report = Instant::now();
+        loop {
+            match commands.try_recv() {
+                Ok(line) => {
+                    writeln!(output)?;
+                    output.flush()?;
+                    return line.map(Some);
+                }
+                Err(TryRecvError::Disconnected) => {
+                    writeln!(output)?;
+                    output.flush()?;
+                    return Ok(None);
+                }
+                Err(TryRecvError::Empty) => {}
+            }
+            if position.is_over() {
+                thread::sleep(Duration::from_millis(10));
+            } else {
+                self.analysis.search(ANALYSIS_BATCH);
+            }
+            if last_report.elapsed() >= request.interval {
+                let report = self.analysis_report(request, &position);
+                if !report.is_empty() {
+                    writeln!(output, "{}", report)?;
+                    output.flush()?;
+                }
+                last_report = Instant::now();
+            }
+        }
+    }
+
+    // Single report after a search of the usual length, for callers without a stream to write to
+    fn analysis_snapshot(&mut self, format: AnalysisFormat, arguments: &[&str]) -> Result<String, String> {
+        let request = self.parse_analysis(format, arguments)?;
+        let position = self.analysis_position(request.stone);
+        self.analysis.update_root_after_move(&position);
+        if !position.is_over() {
+            self.analysis.search(ANALYSIS_ITERATIONS);
+        }
+        Ok(self.analysis_report(&request, &position))
+    }
+
+    /*
+        One info block per searched move, most visited first, on a single line, followed by the ownership
+        of every point from the top left when kata-analyze asks for it.
+        The tree has no policy, so every legal move gets the same prior
+    */
+    fn analysis_report(&self, request: &AnalysisRequest, position: &Game) -> String {
+        let mut statistics: Vec<MoveStatistics> = self
+            .analysis
+            .root_statistics()
+            .into_iter()
+            .filter(|move_statistics| move_statistics.visits > 0)
+            .collect();
+        statistics.sort_by_key(|move_statistics| std::cmp::Reverse(move_statistics.visits));
+        // moves the search did not reach are reported without visits, valued like the position itself
+        for played_move in position.available_cases() {
+            if statistics.len() >= request.min_moves {
+                break;
+            }
+            if statistics.iter().all(|move_statistics| move_statistics.played_move != played_move) {
+                statistics.push(MoveStatistics {
+                    played_move,
+                    visits: 0,
+                    win_rate: self.analysis.root_win_rate().unwrap_or(0.5),
+                    score_lead: self.analysis.root_score_lead().unwrap_or(0.0),
+                    variation: vec![played_move],
+                });
+            }
+        }
+        if let Some(max_moves) = request.max_moves {
+            statistics.truncate(max_moves);
+        }
+        let prior = 1.0 / position.available_cases().len().max(1) as f32;
+        let mut report: Vec<String> = vec![];
+        for (order, move_statistics) in statistics.iter().enumerate() {
+            let win_rate = move_statistics.win_rate;
+            let visits = move_statistics.visits as f32;
+            let lcb = if visits > 0.0 {
+                (win_rate - 1.96 * (win_rate * (1.0 - win_rate) / visits).sqrt()).max(0.0)
+            } else {
+                0.0
+            };
+            let mut info = format!("info move {} visits {} ", vertex(move_statistics.played_move, self.shape), move_statistics.visits);
+            match request.format {
+                AnalysisFormat::Leela => info.push_str(&format!(
+                    "winrate {:.0} prior {:.0} lcb {:.0}",
+                    10000.0 * win_rate,
+                    10000.0 * prior,
+                    10000.0 * lcb
+                )),
+                AnalysisFormat::KataGo => info.push_str(&format!(
+                    "winrate {:.6} scoreMean {:.2} scoreLead {:.2} prior {:.6} lcb {:.6}",
+                    win_rate, move_statistics.score_lead, move_statistics.score_lead, prior, lcb
+                )),
+            }
+            let variation: Vec<String> = move_statistics
+                .variation
+                .iter()
+                .map(|played_move| vertex(*played_move, self.shape))
+                .collect();
+            info.push_str(&format!(" order {} pv {}", order, variation.join(" ")));
+            report.push(info);
+        }
+        if request.ownership && request.format == AnalysisFormat::KataGo && !report.is_empty() {
+            report.push(self.ownership_report(request.stone, position));
+        }
+        report.join(" ")
+    }
+
+    // Share of the playouts each point ends up owned by the analysed player, minus the share owned by the opponent
+    fn ownership_report(&self, stone: Stone, position: &Game) -> String {
+        let ownership = life::playout_ownership(position, life::DEFAULT_PLAYOUTS);
+        let (own, opponent) = if stone == BLACK_STONE {
+            (&ownership.black, &ownership.white)
+        } else {
+            (&ownership.white, &ownership.black)
+        };
+        let values: Vec<String> = own
+            .iter()
+            .flatten()
+            .zip(opponent.iter().flatten())
+            .map(|(own, opponent)| format!("{:.4}", own - opponent))
+            .collect();
+        format!("ownership {}", values.join(" "))
+    }
+}
+
+impl Default for GtpEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stones::EMPTY;
+
+    fn execute(engine: &mut GtpEngine, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        engine.execute(words[0], &words[1..])
+    }
+
+    fn point(engine: &GtpEngine, vertex: &str) -> Stone {
+        match parse_vertex(vertex, engine.shape) {
+            Some(Move::Play(coords)) => engine.game().get_board()[coords],
+            _ => panic!("{} is not a point", vertex),
+        }
+    }
+
+    #[test]
+    fn boardsize_accepts_valid_sizes_only() {
+        let mut engine = GtpEngine::new();
+        assert_eq!(execute(&mut engine, "boardsize 13"), Ok(String::new()));
+        assert_eq!(engine.game().shape(), (13, 13));
+        assert_eq!(execute(&mut engine, "boardsize 1"), Err("unacceptable size".to_string()));
+        assert_eq!(execute(&mut engine, "boardsize 99"), Err("unacceptable size".to_string()));
+        assert_eq!(execute(&mut engine, "boardsize nine"), Err("syntax error".to_string()));
+        assert_eq!(engine.game().shape(), (13, 13));
+    }
+
+    #[test]
+    fn play_and_undo() {
+        let mut engine = GtpEngine::new();
+        execute(&mut engine, "boardsize 9").unwrap();
+        assert_eq!(execute(&mut engine, "play b D4"), Ok(String::new()));
+        assert_eq!(point(&engine, "D4"), BLACK_STONE);
+        assert_eq!(execute(&mut engine, "play w D4"), Err("illegal move".to_string()));
+        assert_eq!(execute(&mut engine, "play w Z4"), Err("syntax error".to_string()));
+        assert_eq!(engine.game().move_number(), 1);
+
+        // a second black move gets a white pass in between, taken back with it
+        assert_eq!(execute(&mut engine, "play b E5"), Ok(String::new()));
+        assert_eq!(engine.game().move_number(), 3);
+        assert_eq!(execute(&mut engine, "undo"), Ok(String::new()));
+        assert_eq!(engine.game().move_number(), 1);
+        assert_eq!(point(&engine, "E5"), EMPTY);
+        assert_eq!(execute(&mut engine, "undo"), Ok(String::new()));
+        assert_eq!(execute(&mut engine, "undo"), Err("cannot undo".to_string()));
+    }
+
+    #[test]
+    fn illegal_move_right_after_the_handicap() {
+        let mut engine = GtpEngine::new();
+        execute(&mut engine, "boardsize 9").unwrap();
+        assert_eq!(execute(&mut engine, "fixed_handicap 2"), Ok("C3 G7".to_string()));
+        assert_eq!(execute(&mut engine, "play w C3"), Err("illegal move".to_string()));
+        assert_eq!(engine.game().move_number(), 0);
+        // Black moving first after the handicap gets a white pass, refused along with the move
+        assert_eq!(execute(&mut engine, "play b G7"), Err("illegal move".to_string()));
+        assert_eq!(engine.game().move_number(), 0);
+        assert_eq!(execute(&mut engine, "play w E5"), Ok(String::new()));
+    }
+
+    #[test]
+    fn fixed_handicap_needs_an_empty_board() {
+        let mut engine = GtpEngine::new();
+        execute(&mut engine, "boardsize 19").unwrap();
+        assert_eq!(execute(&mut engine, "fixed_handicap 1"), Err("invalid number of stones".to_string()));
+        assert_eq!(execute(&mut engine, "fixed_handicap 10"), Err("invalid number of stones".to_string()));
+        assert_eq!(execute(&mut engine, "fixed_handicap 4"), Ok("D4 Q16 D16 Q4".to_string()));
+        assert_eq!(point(&engine, "Q16"), BLACK_STONE);
+        assert_eq!(execute(&mut engine, "fixed_handicap 2"), Err("board not empty".to_string()));
+        execute(&mut engine, "clear_board").unwrap();
+        execute(&mut engine, "boardsize 7").unwrap();
+        assert_eq!(execute(&mut engine, "fixed_handicap 5"), Err("invalid number of stones".to_string()));
+    }
+
+    #[test]
+    fn genmove_plays_for_the_given_color() {
+        let mut engine = GtpEngine::new();
+        execute(&mut engine, "boardsize 5").unwrap();
+        execute(&mut engine, "play b C3").unwrap();
+        let answer = execute(&mut engine, "genmove w").unwrap();
+        let last_record = engine.game().last_record().unwrap();
+        assert_eq!(last_record.color, WHITE_STONE);
+        assert_eq!(vertex(last_record.played_move, engine.shape), answer);
+        assert_ne!(answer, "C3");
+        assert_eq!(execute(&mut engine, "genmove x"), Err("syntax error".to_string()));
+    }
+
+    #[test]
+    fn genmove_does_not_take_back_the_ko() {
+        let mut engine = GtpEngine::new();
+        execute(&mut engine, "boardsize 5").unwrap();
+        for line in ["b B5", "w C5", "b A4", "w D4", "b B3", "w C3", "b C4", "w B4"] {
+            assert_eq!(execute(&mut engine, &format!("play {}", line)), Ok(String::new()));
+        }
+        assert_eq!(point(&engine, "C4"), EMPTY);
+        let answer = execute(&mut engine, "genmove b").unwrap();
+        assert_ne!(answer, "C4");
+        let last_record = engine.game().last_record().unwrap();
+        assert_eq!((last_record.color, vertex(last_record.played_move, engine.shape)), (BLACK_STONE, answer));
+    }
+
+    #[test]
+    fn time_left_is_checked() {
+        let mut engine = GtpEngine::new();
+        assert_eq!(execute(&mut engine, "known_command time_left"), Ok("true".to_string()));
+        execute(&mut engine, "time_settings 600 30 5").unwrap();
+        assert_eq!(execute(&mut engine, "time_left b 120 0"), Ok(String::new()));
+        assert_eq!(execute(&mut engine, "time_left white 25 3"), Ok(String::new()));
+        assert_eq!(execute(&mut engine, "time_left x 25 3"), Err("syntax error".to_string()));
+        assert_eq!(execute(&mut engine, "time_left b soon 0"), Err("syntax error".to_string()));
+        assert_eq!(execute(&mut engine, "time_left b 25"), Err("syntax error".to_string()));
+    }
+
+    fn analysis_request(engine: &GtpEngine, line: &str) -> Result<AnalysisRequest, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        engine.parse_analysis(AnalysisFormat::from_command(words[0]).unwrap(), &words[1..])
+    }
+
+    #[test]
+    fn analysis_arguments() {
+        let engine = GtpEngine::new();
+        let request = analysis_request(&engine, "lz-analyze 50").unwrap();
+        assert_eq!((request.format, request.stone), (AnalysisFormat::Leela, BLACK_STONE));
+        assert_eq!(request.interval, Duration::from_millis(500));
+        assert_eq!((request.max_moves, request.min_moves, request.ownership), (None, 0, false));
+
+        let line = "kata-analyze W interval 20 maxmoves 3 minmoves 2 ownership true";
+        let request = analysis_request(&engine, line).unwrap();
+        assert_eq!((request.format, request.stone), (AnalysisFormat::KataGo, WHITE_STONE));
+        assert_eq!(request.interval, Duration::from_millis(200));
+        assert_eq!((request.max_moves, request.min_moves, request.ownership), (Some(3), 2, true));
+
+        // unknown keys are skipped with their value, avoid and allow with their three values
+        let line = "kata-analyze b avoid w D4,E5 10 pvVisits true ownership false";
+        let request = analysis_request(&engine, line).unwrap();
+        assert_eq!(request.stone, BLACK_STONE);
+        assert_eq!(request.interval, DEFAULT_ANALYSIS_INTERVAL);
+        assert!(!request.ownership);
+
+        for line in ["kata-analyze interval soon", "kata-analyze ownership maybe", "lz-analyze 10 maxmoves"] {
+            assert_eq!(analysis_request(&engine, line).err(), Some("syntax error".to_string()));
+        }
+    }
+
+    #[test]
+    fn analysis_reports_follow_the_formats() {
+        let mut engine = GtpEngine::new();
+        execute(&mut engine, "boardsize 5").unwrap();
+        execute(&mut engine, "play b C3").unwrap();
+
+        let report = execute(&mut engine, "kata-analyze 10 maxmoves 3 ownership true").unwrap();
+        let (infos, ownership) = report.split_once(" ownership ").unwrap();
+        let ownership: Vec<f32> = ownership.split(' ').map(|value| value.parse().unwrap()).collect();
+        assert_eq!(ownership.len(), 25);
+        assert!(ownership.iter().all(|value| (-1.0..=1.0).contains(value)));
+        let infos: Vec<&str> = infos.strip_prefix("info ").unwrap().split(" info ").collect();
+        assert_eq!(infos.len(), 3);
+        let mut last_visits = u32::MAX;
+        for (order, info) in infos.iter().enumerate() {
+            let words: Vec<&str> = info.split(' ').collect();
+            let keys: Vec<&str> = words.iter().step_by(2).take(9).copied().collect();
+            assert_eq!(keys, ["move", "visits", "winrate", "scoreMean", "scoreLead", "prior", "lcb", "order", "pv"]);
+            assert!(matches!(parse_vertex(words[1], engine.shape), Some(Move::Play(_)) | Some(Move::Pass)));
+            assert_ne!(words[1], "C3");
+            let visits = words[3].parse::<u32>().unwrap();
+            assert!(visits > 0 && visits <= last_visits);
+            last_visits = visits;
+            assert!((0.0..=1.0).contains(&words[5].parse::<f32>().unwrap()));
+            assert_eq!(words[7], words[9]);
+            assert_eq!(words[15], order.to_string());
+            assert_eq!(words[17], words[1]);
+        }
+
+        // the 24 empty points and the pass are all the moves there are
+        let report = execute(&mut engine, "lz-analyze w 10 minmoves 30").unwrap();
+        let infos: Vec<&str> = report.strip_prefix("info ").unwrap().split(" info ").collect();
+        assert_eq!(infos.len(), 25);
+        for info in infos {
+            let words: Vec<&str> = info.split(' ').collect();
+            let keys: Vec<&str> = words.iter().step_by(2).take(7).copied().collect();
+            assert_eq!(keys, ["move", "visits", "winrate", "prior", "lcb", "order", "pv"]);
+            assert!(words[5].parse::<u32>().unwrap() <= 10000);
+            assert_eq!(words[7], "400");
+        }
+    }
+
+    #[test]
+    fn responses_follow_the_protocol() {
+        let input = "1 name\n\n# comment\nknown_command play\n2 frobnicate\n3 boardsize\nquit\nname\n";
+        let mut output: Vec<u8> = vec![];
+        GtpEngine::new().run(io::Cursor::new(input), &mut output).unwrap();
+        let expected = "=1 gobot-rs\n\n= true\n\n?2 unknown command\n\n?3 syntax error\n\n= \n\n";
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+}
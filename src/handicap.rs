@@ -0,0 +1,87 @@
+use crate::board::BoardSize;
+use crate::game::Coordinates;
+
+pub const MIN_HANDICAP: usize = 2;
+pub const MAX_HANDICAP: usize = 9;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HandicapPlacement {
+    /// Stones go on the standard star points
+    Fixed,
+    /// The black player chooses where the stones go
+    Free,
+}
+
+/*
+    Largest fixed handicap available on a board, 0 if the board has no star points.
+    As in the GTP specification, boards below 9x9 and even sized boards only have the 4 corner points
+*/
+pub fn max_fixed_handicap((height, width): BoardSize) -> usize {
+    if height != width || height < 7 {
+        0
+    } else if height >= 9 && height % 2 == 1 {
+        MAX_HANDICAP
+    } else {
+        4
+    }
+}
+
+/*
+    Star points used for fixed handicap, in the order of the GTP specification.
+    Corners come first, then the sides for 6 and 8 stones, and the center for odd handicaps from 5 on.
+    Rows are counted from the top of the board, so D4 on a 19x19 board is (15, 3)
+*/
+pub fn fixed_handicap_coordinates(shape: BoardSize, handicap: usize) -> Option<Vec<Coordinates>> {
+    if handicap < MIN_HANDICAP || handicap > max_fixed_handicap(shape) {
+        return None;
+    }
+    let side = shape.0;
+    let edge = if side >= 13 { 3 } else { 2 };
+    let (low, high, middle) = (edge, side - 1 - edge, side / 2);
+
+    let corners = [(high, low), (low, high), (low, low), (high, high)];
+    let left_right = [(middle, low), (middle, high)];
+    let top_bottom = [(high, middle), (low, middle)];
+    let center = (middle, middle);
+
+    let mut coordinates: Vec<Coordinates> = corners.iter().take(handicap.min(4)).copied().collect();
+    if handicap >= 6 {
+        coordinates.extend(left_right);
+    }
+    if handicap >= 8 {
+        coordinates.extend(top_bottom);
+    }
+    if handicap >= 5 && handicap % 2 == 1 {
+        coordinates.push(center);
+    }
+    Some(coordinates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_fixed_handicap_follows_gtp() {
+        assert_eq!(max_fixed_handicap((5, 5)), 0);
+        assert_eq!(max_fixed_handicap((9, 13)), 0);
+        assert_eq!(max_fixed_handicap((7, 7)), 4);
+        assert_eq!(max_fixed_handicap((8, 8)), 4);
+        assert_eq!(max_fixed_handicap((9, 9)), 9);
+        assert_eq!(max_fixed_handicap((10, 10)), 4);
+        assert_eq!(max_fixed_handicap((19, 19)), 9);
+    }
+
+    #[test]
+    fn fixed_handicap_star_points() {
+        assert_eq!(fixed_handicap_coordinates((7, 7), 4), Some(vec![(4, 2), (2, 4), (2, 2), (4, 4)]));
+        assert_eq!(fixed_handicap_coordinates((7, 7), 5), None);
+        assert_eq!(fixed_handicap_coordinates((19, 19), 1), None);
+        // D4, Q16, D16, Q4 then the center K10 for 5 stones
+        assert_eq!(
+            fixed_handicap_coordinates((19, 19), 5),
+            Some(vec![(15, 3), (3, 15), (3, 3), (15, 15), (9, 9)])
+        );
+        assert_eq!(fixed_handicap_coordinates((19, 19), 9).map(|stones| stones.len()), Some(9));
+    }
+}
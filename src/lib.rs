@@ -0,0 +1,17 @@
+pub mod benson;
+pub mod board;
+pub mod chains;
+pub mod game;
+pub mod gtp;
+pub mod handicap;
+pub mod life;
+pub mod stones;
+pub mod signals;
+pub mod mcts;
+pub mod players;
+pub mod point;
+pub mod rules;
+pub mod sgf;
+pub mod zobrist;
+#[cfg(test)]
+mod test_support;
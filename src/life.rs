@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+use crate::game::{Chain, Coordinates, Game};
+use crate::mcts::playout::random_playout;
+use crate::stones::{BLACK_STONE, WHITE_STONE};
+
+pub const DEFAULT_PLAYOUTS: u32 = 50;
+// Share of playouts in which a chain must end up owned by the opponent to be considered dead
+const DEAD_THRESHOLD: f32 = 0.5;
+// Share of playouts in which a liberty must stay neutral for its chain to be considered in seki
+const SEKI_THRESHOLD: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChainStatus {
+    Alive,
+    Dead,
+    Seki,
+}
+
+#[derive(Clone, Debug)]
+pub struct ChainEstimate {
+    pub chain: Chain,
+    pub status: ChainStatus,
+    // share of playouts in which the points of the chain stayed owned by its color
+    pub ownership: f32,
+}
+
+// Share of playouts in which every point ended up owned by Black and by White
+pub struct OwnershipMap {
+    pub black: Vec<Vec<f32>>,
+    pub white: Vec<Vec<f32>>,
+}
+
+impl OwnershipMap {
+    fn neutral(&self, (x, y): Coordinates) -> f32 {
+        1.0 - self.black[x][y] - self.white[x][y]
+    }
+}
+
+pub fn playout_ownership(game: &Game, playouts: u32) -> OwnershipMap {
+    let mut state = game.clone();
+    state.set_display(false);
+    state.resume_play();
+    let (height, width) = game.shape();
+    let mut ownership_map = OwnershipMap {
+        black: vec![vec![0.0; width]; height],
+        white: vec![vec![0.0; width]; height],
+    };
+    if playouts == 0 {
+        return ownership_map;
+    }
+    let share = 1.0 / playouts as f32;
+    for _ in 0..playouts {
+        let mut playout_state = state.clone();
+        random_playout(&mut playout_state);
+        for (x, row) in playout_state.ownership().iter().enumerate() {
+            for (y, owner) in row.iter().enumerate() {
+                if *owner == BLACK_STONE {
+                    ownership_map.black[x][y] += share;
+                } else if *owner == WHITE_STONE {
+                    ownership_map.white[x][y] += share;
+                }
+            }
+        }
+    }
+    ownership_map
+}
+
+/*
+    Classifies every chain of the position from the ownership statistics of random playouts.
+    A chain whose points mostly end up owned by the opponent is dead.
+    A surviving chain with a liberty that mostly stays neutral is in seki, as playouts never
+    fill the shared liberties of a seki (it would be a self-atari). Chains touching a seki point of the
+    current position are in seki as well.
+    Chains whose fate is already decided by Benson's algorithm are classified without looking at the playouts.
+*/
+pub fn estimate_life_and_death(game: &Game, playouts: u32) -> Vec<ChainEstimate> {
+    let ownership_map = playout_ownership(game, playouts);
+    let mut pass_alive: HashSet<Coordinates> = HashSet::new();
+    let mut unconditionally_dead: HashSet<Coordinates> = HashSet::new();
+    for color in [BLACK_STONE, WHITE_STONE] {
+        let life = game.unconditional_life(color);
+        pass_alive.extend(life.alive_stones);
+        unconditionally_dead.extend(life.territory);
+    }
+    let seki_points = game.seki_points();
+    game.chains()
+        .into_iter()
+        .map(|chain| {
+            let (own_map, opponent_map) = if chain.color == BLACK_STONE {
+                (&ownership_map.black, &ownership_map.white)
+            } else {
+                (&ownership_map.white, &ownership_map.black)
+            };
+            let n_stones = chain.stones.len() as f32;
+            let ownership = chain.stones.iter().map(|(x, y)| own_map[*x][*y]).sum::<f32>() / n_stones;
+            let lost = chain.stones.iter().map(|(x, y)| opponent_map[*x][*y]).sum::<f32>() / n_stones;
+            let status = if pass_alive.contains(&chain.anchor) {
+                ChainStatus::Alive
+            } else if unconditionally_dead.contains(&chain.anchor) || lost > DEAD_THRESHOLD {
+                ChainStatus::Dead
+            } else if chain
+                .liberties
+                .iter()
+                .any(|liberty| seki_points.contains(liberty) || ownership_map.neutral(*liberty) > SEKI_THRESHOLD)
+            {
+                ChainStatus::Seki
+            } else {
+                ChainStatus::Alive
+            };
+            ChainEstimate { chain, status, ownership }
+        })
+        .collect()
+}
+
+pub fn dead_stones(estimates: &[ChainEstimate]) -> Vec<Coordinates> {
+    estimates
+        .iter()
+        .filter(|estimate| estimate.status == ChainStatus::Dead)
+        .flat_map(|estimate| estimate.chain.stones.iter().copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+    use crate::test_support::position;
+
+    fn status_at(estimates: &[ChainEstimate], coords: Coordinates) -> ChainStatus {
+        estimates
+            .iter()
+            .find(|estimate| estimate.chain.stones.contains(&coords))
+            .map(|estimate| estimate.status)
+            .unwrap()
+    }
+
+    #[test]
+    fn stone_inside_a_living_group_is_dead() {
+        let game = position(
+            &[
+                "O.X.X",
+                "XXXXX",
+                ".....",
+                ".....",
+                ".....",
+            ],
+            Rules::default(),
+        );
+        let estimates = estimate_life_and_death(&game, DEFAULT_PLAYOUTS);
+        assert_eq!(status_at(&estimates, (0, 0)), ChainStatus::Dead);
+        assert_eq!(status_at(&estimates, (1, 0)), ChainStatus::Alive);
+        assert_eq!(dead_stones(&estimates), vec![(0, 0)]);
+    }
+
+    #[test]
+    fn chains_sharing_liberties_are_in_seki() {
+        let game = position(
+            &[
+                ".X.OX..",
+                "OOOOX..",
+                "XXXXX..",
+                ".......",
+                ".......",
+                ".......",
+                ".......",
+            ],
+            Rules::default(),
+        );
+        let estimates = estimate_life_and_death(&game, DEFAULT_PLAYOUTS);
+        assert_eq!(status_at(&estimates, (0, 1)), ChainStatus::Seki);
+        assert_eq!(status_at(&estimates, (1, 0)), ChainStatus::Seki);
+        assert_eq!(status_at(&estimates, (2, 0)), ChainStatus::Alive);
+        assert!(dead_stones(&estimates).is_empty());
+    }
+}
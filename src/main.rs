@@ -1,19 +1,27 @@
 use std::rc::Rc;
-use crate::game::Game;
-use crate::players::bot::Bot;
-use crate::players::human::Human;
-use crate::players::player::Player;
-use crate::stones::{BLACK_STONE, WHITE_STONE};
-
-mod board;
-mod game;
-mod stones;
-mod signals;
-mod mcts;
-mod players;
+use gobot_rs::board::DEFAULT_SIDE;
+use gobot_rs::game::Game;
+use gobot_rs::players::bot::Bot;
+use gobot_rs::players::human::Human;
+use gobot_rs::players::player::Player;
+use gobot_rs::rules::Rules;
+use gobot_rs::sgf::writer;
+use gobot_rs::stones::{BLACK_STONE, WHITE_STONE};
 
 fn main() {
     let players: [Rc<Box<dyn Player>>; 2] = [Rc::new(Box::new(Human::new(BLACK_STONE))), Rc::new(Box::new(Bot::new(WHITE_STONE)))];
-    let mut game = Game::new(players, true, 7.5);
-    game.game()
+    let mut game = match Game::new(players, (DEFAULT_SIDE, DEFAULT_SIDE), true, Rules::default()) {
+        Ok(game) => game,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    game.game();
+    // the game record is saved to the file given as first argument, if any
+    if let Some(path) = std::env::args().nth(1) {
+        if let Err(e) = writer::save_game(&game, &path) {
+            eprintln!("Could not save the game to {}: {}", path, e);
+        }
+    }
 }
\ No newline at end of file
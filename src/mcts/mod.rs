@@ -4,6 +4,7 @@ use crate::game::Move;
 use crate::mcts::node::Node;
 
 pub mod tree;
-mod node;
+pub mod node;
+pub mod playout;
 
 type MoveNodeRef = Rc<RefCell<(Move, Node)>>;
\ No newline at end of file
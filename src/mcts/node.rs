@@ -1,79 +1,105 @@
 use std::cell::RefCell;
 use std::rc::Rc;
-use rand::prelude::IndexedRandom;
+use rand::prelude::SliceRandom;
 use crate::game::{Game, Move};
-use crate::mcts::{MoveNodeRef, tree::Tree};
+use crate::mcts::{MoveNodeRef, playout::random_playout, tree::Tree};
 use crate::players::player::Player;
+use crate::signals::Signals;
+use crate::stones::{BLACK_STONE, WHITE_STONE};
 
 #[derive(Clone, Default)]
 pub struct Node {
     pub(crate) state: Game,
     pub(crate) children: Vec<MoveNodeRef>,
+    // moves of the state without a child yet, in random order, None until the node is first expanded
+    untried_moves: Option<Vec<Move>>,
     eval: f32,
     pub(crate) won: f32,
-    pub(crate) total: f32
+    pub(crate) total: f32,
+    // sum of Black's score minus White's at the end of the simulations through the node
+    score_total: f32,
 }
 
 impl Node {
     pub fn new() -> Node {
-        let state= Game::default();
+        let mut state= Game::default();
+        state.set_display(false);
         let score = state.calculate_scores_difference();
-        Node { state, children:vec![], eval: score, won: 0.0, total: 0.0 }
+        Self::from(state, vec![], score, 0.0, 0.0)
     }
 
     pub fn from(state: Game, children: Vec<MoveNodeRef>, eval: f32, won:f32, total:f32) -> Node {
         Node {
             state,
             children,
+            untried_moves: None,
             eval,
             won,
-            total
+            total,
+            score_total: 0.0,
         }
     }
 
-    pub(crate) fn expand(&mut self) {
-        let random_child = self.generate_random_child();
-        self.add_child(Rc::new(RefCell::new(random_child.unwrap())));
+    // Adds the child of a move not tried yet, None once every move of the state has its child
+    pub(crate) fn expand(&mut self) -> Option<MoveNodeRef> {
+        let random_child = Rc::new(RefCell::new(self.generate_random_child()?));
+        self.add_child(Rc::clone(&random_child));
+        Some(random_child)
     }
 
-    fn generate_random_choice(&self) -> Move {
-        let mut rng = rand::rng();
-        let available_cases = self.state.available_cases(self.state.get_current_player());
-        let chosen_move = available_cases.choose(&mut rng);
-        match chosen_move {
-            Some(c) => *c,
-            None => None
-        }
+    // Whether some move of the state may still get a child
+    pub(crate) fn can_expand(&self) -> bool {
+        !self.is_over() && self.untried_moves.as_ref().is_none_or(|moves| !moves.is_empty())
     }
 
-    fn generate_random_child(&self) -> Option<(Move, Node)> {
-        if !self.is_over() {
-            let random_choice = self.generate_random_choice();
+    // Moves worth a child: every available move but the ones on points settled by Benson's algorithm
+    fn candidate_moves(state: &Game) -> Vec<Move> {
+        let settled = state.settled_points();
+        state
+            .available_cases()
+            .into_iter()
+            .filter(|case| match case {
+                Move::Play((x, y)) => !settled[*x][*y],
+                _ => true,
+            })
+            .collect()
+    }
+
+    fn generate_random_child(&mut self) -> Option<(Move, Node)> {
+        if self.is_over() {
+            return None;
+        }
+        let untried_moves = self.untried_moves.get_or_insert_with(|| {
+            let mut moves = Self::candidate_moves(&self.state);
+            moves.shuffle(&mut rand::rng());
+            moves
+        });
+        // moves the rules refuse, like taking a ko back, are dropped
+        while let Some(random_choice) = untried_moves.pop() {
             let mut state_clone = self.state.clone();
-            let mut result_step = state_clone.step(random_choice);
-            while result_step.is_err() {
-                if state_clone.is_over() {
-                    break;
-                } else {
-                    let random_choice = self.generate_random_choice();
-                    state_clone = self.state.clone();
-                    result_step = state_clone.step(random_choice);
-                }
+            if let Ok(()) | Err(Signals::DoublePass) = state_clone.step(random_choice) {
+                let eval = state_clone.calculate_scores_difference();
+                return Some((random_choice, Self::from(state_clone, vec![], eval, 0.0, 0.0)));
             }
-            let eval = state_clone.calculate_scores_difference();
-            return Some((random_choice, Self::from(state_clone, vec![], eval, 0.0, 0.0)))
         }
         None
     }
 
-    pub(crate) fn run_simulation(&self) -> Option<Rc<Box<dyn Player>>> {
-        let mut current_node = self.clone();
-        while !current_node.state.is_over() {
-            let child = current_node.generate_random_child();
-            (_, current_node) = child.unwrap();
-        }
-
-        current_node.state.winner()
+    // Winner of a random game from the state, with Black's score minus White's at its end
+    pub(crate) fn run_simulation(&self) -> (Option<Rc<Box<dyn Player>>>, f32) {
+        // The playout is stepped in place on a single copy of the state
+        let mut state = self.state.clone();
+        random_playout(&mut state);
+        // playouts end with passes, never with a resignation, so the score decides
+        let score_difference = state.calculate_scores_difference();
+        let winner = if score_difference > 0.0 {
+            Some(state.find_player(BLACK_STONE))
+        } else if score_difference < 0.0 {
+            Some(state.find_player(WHITE_STONE))
+        } else {
+            None
+        };
+        (winner, score_difference)
     }
 
     fn add_child(&mut self, child: MoveNodeRef) {
@@ -85,9 +111,22 @@ impl Node {
     }
 
     pub(crate) fn is_leaf(&self) -> bool {
-        self.children.len() == 0
+        self.children.is_empty()
     }
 
+    pub fn eval(&self) -> f32 { self.eval }
+
+    // Average of Black's score minus White's over the simulations through the node, None before any
+    pub fn mean_score(&self) -> Option<f32> {
+        if self.total > 0.0 {
+            Some(self.score_total / self.total)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn add_score(&mut self, score_difference: f32) { self.score_total += score_difference }
+
     fn is_over(&self) -> bool { self.state.is_over() }
 
     fn increment_total(&mut self) { self.total += 1.0 }
@@ -130,4 +169,42 @@ impl Node {
         worst_child
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+    use crate::test_support::position;
+
+    #[test]
+    fn children_are_labelled_with_the_move_that_leads_to_them() {
+        let mut state = position(
+            &[
+                ".XO..",
+                "X.XO.",
+                ".XO..",
+                ".....",
+                ".....",
+            ],
+            Rules::default(),
+        );
+        state.step(Move::Pass).unwrap();
+        // White takes the ko, Black may not take back at once
+        state.step(Move::Play((1, 1))).unwrap();
+        let mut node = Node::from(state, vec![], 0.0, 0.0, 0.0);
+        let mut played_moves: Vec<Move> = vec![];
+        while let Some(child) = node.expand() {
+            let (played_move, child) = &*child.borrow();
+            let mut replayed = node.state.clone();
+            assert!(matches!(replayed.step(*played_move), Ok(()) | Err(Signals::DoublePass)));
+            assert_eq!(replayed.position_hash(), child.state.position_hash());
+            assert!(!played_moves.contains(played_move));
+            played_moves.push(*played_move);
+        }
+        assert!(!node.can_expand());
+        assert!(!played_moves.contains(&Move::Play((1, 2))));
+        // every empty point but the ko, and the pass
+        assert_eq!(played_moves.len(), 25 - 7 - 1 + 1);
+    }
+}
@@ -0,0 +1,51 @@
+use rand::prelude::SliceRandom;
+use crate::game::{Game, Move};
+
+// Plays random moves on the state until the game is over.
+// Players never fill their own eyes and pass when no other move is left,
+// so that random games end in a reasonable number of moves.
+// Chains are never put in self-atari either, which keeps sekis alive until the end of the playout.
+// Points settled by Benson's algorithm are left alone, so playouts stop as soon as the board is decided,
+// and the stones left inside unconditional territory are marked dead before scoring
+pub fn random_playout(state: &mut Game) {
+    let mut rng = rand::rng();
+    let (height, width) = state.shape();
+    let max_moves = 3 * height * width;
+    let mut n_moves = 0;
+    let mut settled = state.settled_points();
+    while !state.is_over() {
+        if n_moves % height == 0 {
+            settled = state.settled_points();
+        }
+        let player_stone = state.get_current_player().get_stone();
+        let mut candidates: Vec<Move> = state
+            .available_cases()
+            .into_iter()
+            .filter(|case| match case {
+                Move::Play(coords) => {
+                    !settled[coords.0][coords.1]
+                        && state.eye_owner(*coords) != Some(player_stone)
+                        && !state.induces_suicide(*coords)
+                        && !state.is_self_atari(*coords)
+                }
+                _ => false,
+            })
+            .collect();
+        candidates.shuffle(&mut rng);
+        let mut played = false;
+        if n_moves < max_moves {
+            for candidate in candidates {
+                if state.step(candidate).is_ok() {
+                    played = true;
+                    break;
+                }
+            }
+        }
+        if !played {
+            let _ = state.step(Move::Pass);
+        }
+        n_moves += 1;
+    }
+    let dead_stones = state.unconditionally_dead_stones();
+    state.set_dead_stones(&dead_stones);
+}
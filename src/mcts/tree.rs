@@ -2,12 +2,25 @@ use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::f32::consts::SQRT_2;
 use std::rc::Rc;
-use crate::board::Board;
 use crate::game::{Game, Move};
 use crate::mcts::{MoveNodeRef, node::Node};
+use crate::stones::BLACK_STONE;
 
 
 
+// What the search learnt about a move of the root
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveStatistics {
+    pub played_move: Move,
+    pub visits: u32,
+    // share of the simulations won by the player making the move
+    pub win_rate: f32,
+    // points the player making the move leads by after it
+    pub score_lead: f32,
+    // the move followed by the moves expected after it
+    pub variation: Vec<Move>,
+}
+
 #[derive(Default)]
 pub struct Tree {
     pub(crate) root: MoveNodeRef,
@@ -16,22 +29,23 @@ pub struct Tree {
 impl Tree {
     pub fn new() -> Tree {
         Tree {
-            root: Rc::new(RefCell::new((None, Node::new())))
+            root: Rc::new(RefCell::new((Move::Pass, Node::new())))
         }
     }
 
-    fn from(root: MoveNodeRef) -> Tree {
+    pub fn from(root: MoveNodeRef) -> Tree {
         Tree {
             root
         }
     }
 
+    // Goes down the most promising children, stopping at the first node with moves left to expand
     fn selection(&self) -> Vec<MoveNodeRef> {
         let mut path = Vec::new();
         let mut current_node = Rc::clone(&self.root);
         path.push(Rc::clone(&current_node));
 
-        while !current_node.borrow().1.is_leaf() {
+        while !current_node.borrow().1.is_leaf() && !current_node.borrow().1.can_expand() {
             let best_child = current_node.borrow().1.most_suited_child_selection();
             path.push(Rc::clone(&best_child));
             current_node = best_child;
@@ -40,34 +54,88 @@ impl Tree {
     }
     pub fn mcts_step(&self) {
         // Selection
-        let leaf_node_path = self.selection();
-        let end_child = leaf_node_path.last().unwrap();
+        let mut path = self.selection();
         // Expansion
-        end_child.borrow_mut().1.expand();
+        let new_child = path.last().unwrap().borrow_mut().1.expand();
+        if let Some(new_child) = new_child {
+            path.push(new_child);
+        }
         // Simulation
-        let winner_opt = end_child.borrow().1.run_simulation();
-        let leaf_node_path_reverse = leaf_node_path.iter().rev();
+        let (winner_opt, score_difference) = path.last().unwrap().borrow().1.run_simulation();
         // Backpropagation
-        match winner_opt {
-            Some(winner) => {
-                for node in leaf_node_path_reverse {
-                    if winner == node.borrow().1.state.get_current_player() {
-                        node.borrow_mut().1.increment_won()
-                    }
-                    else {
-                        node.borrow_mut().1.increment_lost()
-                    }
+        for node in path.iter().rev() {
+            node.borrow_mut().1.add_score(score_difference);
+            match &winner_opt {
+                Some(winner) if *winner == node.borrow().1.state.get_current_player() => {
+                    node.borrow_mut().1.increment_won()
                 }
+                Some(_) => node.borrow_mut().1.increment_lost(),
+                None => node.borrow_mut().1.increment_draw(),
             }
-            None => {
-                for node in leaf_node_path_reverse {
-                    node.borrow_mut().1.increment_draw()
+        }
+    }
+
+    pub fn search(&self, iterations: u32) {
+        for _ in 0..iterations {
+            self.mcts_step();
+        }
+    }
+
+    // Statistics of every move explored from the root
+    pub fn root_statistics(&self) -> Vec<MoveStatistics> {
+        let root = self.root.borrow();
+        let mover_is_black = root.1.state.get_current_player().get_stone() == BLACK_STONE;
+        root.1
+            .children
+            .iter()
+            .map(|child| {
+                let (played_move, node) = &*child.borrow();
+                // nodes count the wins of the player to move there, the opponent of the one who made the move
+                let win_rate = if node.total > 0.0 { (node.total - node.won) / node.total } else { 0.0 };
+                // the count of the position only stands in for the playouts until there are some
+                let black_lead = node.mean_score().unwrap_or(node.eval());
+                MoveStatistics {
+                    played_move: *played_move,
+                    visits: node.total as u32,
+                    win_rate,
+                    score_lead: if mover_is_black { black_lead } else { -black_lead },
+                    variation: Self::variation_from(child),
+                }
+            })
+            .collect()
+    }
+
+    // Moves expected from the root, following the most visited child at every node
+    pub fn principal_variation(&self) -> Vec<Move> {
+        let mut variation = Self::variation_from(&self.root);
+        variation.remove(0);
+        variation
+    }
+
+    // Move of the node followed by the most visited child at every node below it
+    fn variation_from(node: &MoveNodeRef) -> Vec<Move> {
+        let mut variation: Vec<Move> = vec![node.borrow().0];
+        let mut current_node = Rc::clone(node);
+        loop {
+            let most_visited = current_node
+                .borrow()
+                .1
+                .children
+                .iter()
+                .filter(|child| child.borrow().1.total > 0.0)
+                .max_by(|a, b| a.borrow().1.total.total_cmp(&b.borrow().1.total))
+                .map(Rc::clone);
+            match most_visited {
+                Some(child) => {
+                    variation.push(child.borrow().0);
+                    current_node = child;
                 }
+                None => return variation,
             }
         }
     }
 
-    fn get_leaves(&self) -> Vec<MoveNodeRef> {
+    pub fn get_leaves(&self) -> Vec<MoveNodeRef> {
         // Breadth first search
         let mut res: Vec<MoveNodeRef> = vec![];
         let mut queue: VecDeque<MoveNodeRef> = VecDeque::new();
@@ -90,20 +158,52 @@ impl Tree {
         self.root.borrow().1.least_winning_child()
     }
 
+    // Share of the simulations through the root won by the player to move, None before any simulation
+    pub fn root_win_rate(&self) -> Option<f32> {
+        let root = &self.root.borrow().1;
+        if root.total > 0.0 {
+            Some(root.won / root.total)
+        } else {
+            None
+        }
+    }
+
+    // Points the player to move leads by on average in the simulations through the root, None before any simulation
+    pub fn root_score_lead(&self) -> Option<f32> {
+        let root = &self.root.borrow().1;
+        let black_lead = root.mean_score()?;
+        Some(if root.state.get_current_player().get_stone() == BLACK_STONE { black_lead } else { -black_lead })
+    }
+
     pub fn replace_root(&mut self, move_node_ref: MoveNodeRef) {
         self.root = move_node_ref
     }
 
     fn find_state_in_root_children(&self, game: &Game) -> Option<MoveNodeRef> {
         for child in self.root.borrow().1.children.iter() {
-            if child.borrow().1.state == *game {
+            // histories are compared too, so that a position reached by another sequence gets a fresh tree
+            let child_state = &child.borrow().1.state;
+            if child_state.position_hash() == game.position_hash() && child_state.position_history() == game.position_history() {
                 return Some(Rc::clone(child));
             }
         }
         None
     }
 
+    fn is_root_state(&self, game: &Game) -> bool {
+        let root_state = &self.root.borrow().1.state;
+        // the players are compared too, so that the default root of a new tree is never taken for a real game
+        Rc::ptr_eq(&root_state.get_current_player(), &game.get_current_player())
+            && root_state.rules() == game.rules()
+            && root_state.position_hash() == game.position_hash()
+            && root_state.moves().eq(game.moves())
+    }
+
     pub fn update_root_after_move(&mut self, game: &Game) {
+        // the search goes on from where it was if nothing was played since
+        if self.is_root_state(game) {
+            return;
+        }
         let game_in_children_option = self.find_state_in_root_children(game);
 
         match game_in_children_option {
@@ -111,8 +211,10 @@ impl Tree {
                 self.replace_root(child);
             }
             None => {
-                let new_root = Node::from(game.clone(), vec![], game.calculate_scores_difference(), 0.0, 0.0);
-                let new_root_ref = Rc::new(RefCell::new((None, new_root)));
+                let mut state = game.clone();
+                state.set_display(false);
+                let new_root = Node::from(state, vec![], game.calculate_scores_difference(), 0.0, 0.0);
+                let new_root_ref = Rc::new(RefCell::new((Move::Pass, new_root)));
                 self.replace_root(new_root_ref);
             }
         }
@@ -125,4 +227,41 @@ impl Tree {
 
         w/n + c*(parent.total.ln()/n).sqrt()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+    use crate::test_support::players;
+
+    fn empty_board_tree() -> Tree {
+        let game = Game::new(players(), (5, 5), false, Rules::default()).unwrap();
+        let mut tree = Tree::new();
+        tree.update_root_after_move(&game);
+        tree
+    }
+
+    #[test]
+    fn root_gets_every_move_before_the_search_goes_deeper() {
+        let tree = empty_board_tree();
+        // every point and the pass
+        tree.search(26);
+        let statistics = tree.root_statistics();
+        assert_eq!(statistics.len(), 26);
+        assert!(statistics.iter().all(|move_statistics| move_statistics.visits == 1));
+        tree.search(100);
+        assert_eq!(tree.root_statistics().len(), 26);
+        assert!(tree.principal_variation().len() > 1);
+    }
+
+    #[test]
+    fn score_lead_averages_the_playouts() {
+        let tree = empty_board_tree();
+        tree.search(26);
+        // counted as it stands, a lone black stone owns the whole board: 25 points minus a komi of 7.5
+        let statistics = tree.root_statistics();
+        assert!(statistics.iter().any(|move_statistics| (move_statistics.score_lead - 17.5).abs() > 1e-3));
+        assert!(statistics.iter().all(|move_statistics| move_statistics.score_lead.abs() <= 32.5));
+        assert!(tree.root_score_lead().is_some());
+    }
+}
@@ -1,17 +1,37 @@
-use std::cell::RefCell;
-use crate::board::Board;
-use crate::game::{Game, Move};
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use crate::game::{Coordinates, Game, Move};
+use crate::life;
 use crate::mcts::tree::Tree;
-use crate::players::player::Player;
+use crate::players::player::{DeadStonesResponse, Player};
 use crate::stones::{get_stone_name_from_stone, Stone};
 
 const N_ITER: u32 = 10;
+const N_OWNERSHIP_PLAYOUTS: u32 = 50;
+// Share of the computed move time actually used, to keep some slack for the communication
+const TIME_MARGIN: f32 = 0.8;
+// Fewest moves the main time left is spread over, so that the end of the game still gets some of it
+const MIN_MOVES_LEFT: u32 = 10;
+pub const DEFAULT_RESIGN_THRESHOLD: f32 = 0.1;
+pub const DEFAULT_RESIGN_MOVES: u32 = 5;
 
 
 pub struct Bot {
     name: String,
     stone: Stone,
-    tree: RefCell<Tree>
+    tree: RefCell<Tree>,
+    // dead stones estimated for the last analysed position, keyed by its hash
+    dead_stones_estimate: RefCell<Option<(u64, Vec<Coordinates>)>>,
+    // the bot resigns once its root win rate stayed below the threshold for that many moves in a row
+    resign_threshold: f32,
+    resign_moves: u32,
+    low_win_rate_moves: Cell<u32>,
+    last_win_rate: Cell<Option<f32>>,
+    // main time and Canadian byo-yomi period with its number of stones, None without time limit
+    time_settings: Cell<Option<(Duration, Duration, u32)>>,
+    // time left on the clock and stones to play in it, no stones meaning main time
+    time_left: Cell<Option<(Duration, u32)>>,
 }
 
 impl Bot {
@@ -19,10 +39,23 @@ impl Bot {
         Bot {
             name: get_stone_name_from_stone(stone).to_string(),
             tree: RefCell::new(Tree::new()),
-            stone
+            stone,
+            dead_stones_estimate: RefCell::new(None),
+            resign_threshold: DEFAULT_RESIGN_THRESHOLD,
+            resign_moves: DEFAULT_RESIGN_MOVES,
+            low_win_rate_moves: Cell::new(0),
+            last_win_rate: Cell::new(None),
+            time_settings: Cell::new(None),
+            time_left: Cell::new(None),
         }
     }
 
+    // A threshold of 0 disables resignation
+    pub fn set_resign_threshold(&mut self, threshold: f32, consecutive_moves: u32) {
+        self.resign_threshold = threshold;
+        self.resign_moves = consecutive_moves;
+    }
+
     /*
         With area scoring, the bots should continue playing unless playing leads to a situation where a group loses an eye
         In that case, the bot should pass
@@ -38,23 +71,95 @@ impl Bot {
 
     fn next_best_move(&self, game: &Game) -> Move {
         self.refresh_state(game);
-        self.think();
+        let start = Instant::now();
+        self.think(self.move_time(game));
+        self.spend_time(start.elapsed());
+        if self.should_resign() {
+            return Move::Resign;
+        }
         let best_move_ref = self.tree.borrow().best_move();
-        let best_move = best_move_ref.borrow().0.clone();
+        let best_move = best_move_ref.borrow().0;
         self.tree.borrow_mut().replace_root(best_move_ref);
         best_move
     }
     
+    fn should_resign(&self) -> bool {
+        let win_rate = self.tree.borrow().root_win_rate();
+        self.last_win_rate.set(win_rate);
+        match win_rate {
+            Some(win_rate) if win_rate < self.resign_threshold => {
+                self.low_win_rate_moves.set(self.low_win_rate_moves.get() + 1)
+            }
+            _ => self.low_win_rate_moves.set(0),
+        }
+        self.resign_moves > 0 && self.low_win_rate_moves.get() >= self.resign_moves
+    }
+
     fn refresh_state(&self, game: &Game) {
         // find a node in children of root which game state represents the current state of the game
         self.tree.borrow_mut().update_root_after_move(game)
     }
     
-    fn think(&self) {
+    /*
+        Time to spend on the next move. In main time, the time left is spread over the moves left to the bot,
+        taken as half the empty points, and a byo-yomi stone always gets at least its share of the period.
+        In byo-yomi, the time left is shared by the stones left to play in the period.
+        The bot always runs at least N_ITER iterations, so it may overrun very short time budgets
+    */
+    fn move_time(&self, game: &Game) -> Option<Duration> {
+        let (_, byo_yomi_time, byo_yomi_stones) = self.time_settings.get()?;
+        let (time_left, stones_left) = self.time_left.get()?;
+        let budget = if stones_left > 0 {
+            time_left / stones_left
+        } else {
+            let empty_points = game.get_board().rows().iter().flatten().filter(|stone| stone.is_none()).count();
+            let moves_left = (empty_points as u32 / 2).max(MIN_MOVES_LEFT);
+            let byo_yomi_share = if byo_yomi_stones > 0 { byo_yomi_time / byo_yomi_stones } else { Duration::ZERO };
+            (time_left / moves_left).max(byo_yomi_share)
+        };
+        Some(budget.mul_f32(TIME_MARGIN))
+    }
+
+    // Keeps the clock running between two time_left commands, for controllers that do not send them
+    fn spend_time(&self, elapsed: Duration) {
+        let (Some((_, byo_yomi_time, byo_yomi_stones)), Some((time_left, stones_left))) =
+            (self.time_settings.get(), self.time_left.get())
+        else {
+            return;
+        };
+        let time_left = match stones_left {
+            // the main time is over, byo-yomi starts
+            0 if time_left <= elapsed => (byo_yomi_time, byo_yomi_stones),
+            0 => (time_left - elapsed, 0),
+            // the last stone of the period was played, a new period starts
+            1 => (byo_yomi_time, byo_yomi_stones),
+            _ => (time_left.saturating_sub(elapsed), stones_left - 1),
+        };
+        self.time_left.set(Some(time_left));
+    }
+
+    fn think(&self, move_time: Option<Duration>) {
+        let deadline = move_time.map(|move_time| Instant::now() + move_time);
         let tree_borrow_mut = self.tree.borrow_mut();
-        for _ in 0..N_ITER {
+        let mut iterations = 0;
+        while iterations < N_ITER || deadline.is_some_and(|deadline| Instant::now() < deadline) {
             tree_borrow_mut.mcts_step();
+            iterations += 1;
+        }
+    }
+
+    fn estimate_dead_stones(&self, game: &Game) -> Vec<Coordinates> {
+        let position_hash = game.position_hash();
+        if let Some((estimated_hash, dead_stones)) = self.dead_stones_estimate.borrow().as_ref() {
+            if *estimated_hash == position_hash {
+                return dead_stones.clone();
+            }
         }
+
+        let estimates = life::estimate_life_and_death(game, N_OWNERSHIP_PLAYOUTS);
+        let dead_stones = life::dead_stones(&estimates);
+        *self.dead_stones_estimate.borrow_mut() = Some((position_hash, dead_stones.clone()));
+        dead_stones
     }
 }
 
@@ -64,4 +169,83 @@ impl Player for Bot {
     fn choose_case(&self, game: &Game) -> Move {
         self.next_best_move(game)
     }
-}
\ No newline at end of file
+    fn set_time_settings(&self, main_time: Duration, byo_yomi_time: Duration, byo_yomi_stones: u32) {
+        // GTP gives no time limit as a byo-yomi period without stones
+        let no_limit = byo_yomi_stones == 0 && !byo_yomi_time.is_zero();
+        let no_time = main_time.is_zero() && byo_yomi_time.is_zero();
+        if no_limit || no_time {
+            self.time_settings.set(None);
+            self.time_left.set(None);
+        } else {
+            self.time_settings.set(Some((main_time, byo_yomi_time, byo_yomi_stones)));
+            let time_left = if main_time.is_zero() { (byo_yomi_time, byo_yomi_stones) } else { (main_time, 0) };
+            self.time_left.set(Some(time_left));
+        }
+    }
+    fn set_time_left(&self, time_left: Duration, stones: u32) {
+        // the clock only matters to a game with a time limit
+        if self.time_settings.get().is_some() {
+            self.time_left.set(Some((time_left, stones)));
+        }
+    }
+    fn last_evaluation(&self) -> Option<f32> {
+        self.last_win_rate.get()
+    }
+    fn propose_dead_stones(&self, game: &Game) -> Vec<Coordinates> {
+        self.estimate_dead_stones(game)
+    }
+    fn review_dead_stones(&self, game: &Game, proposal: &[Coordinates]) -> DeadStonesResponse {
+        let estimate = self.estimate_dead_stones(game);
+        let proposal_set: HashSet<&Coordinates> = proposal.iter().collect();
+        let estimate_set: HashSet<&Coordinates> = estimate.iter().collect();
+        if proposal_set == estimate_set {
+            DeadStonesResponse::Accept
+        } else {
+            DeadStonesResponse::Dispute(estimate)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+    use crate::stones::BLACK_STONE;
+    use crate::test_support::players;
+
+    #[test]
+    fn move_time_follows_the_time_left() {
+        let game = Game::new(players(), (19, 19), false, Rules::default()).unwrap();
+        let bot = Bot::new(BLACK_STONE);
+        assert_eq!(bot.move_time(&game), None);
+
+        // sudden death: the time left is spread over half the empty points
+        bot.set_time_settings(Duration::from_secs(600), Duration::ZERO, 0);
+        assert_eq!(bot.move_time(&game), Some((Duration::from_secs(600) / 180).mul_f32(TIME_MARGIN)));
+        bot.set_time_left(Duration::from_secs(60), 0);
+        assert_eq!(bot.move_time(&game), Some((Duration::from_secs(60) / 180).mul_f32(TIME_MARGIN)));
+
+        // byo-yomi: the period is shared by the stones left to play in it
+        bot.set_time_left(Duration::from_secs(30), 5);
+        assert_eq!(bot.move_time(&game), Some(Duration::from_secs(6).mul_f32(TIME_MARGIN)));
+    }
+
+    #[test]
+    fn clock_runs_between_time_left_commands() {
+        let bot = Bot::new(BLACK_STONE);
+        bot.set_time_settings(Duration::from_secs(10), Duration::from_secs(30), 5);
+        bot.spend_time(Duration::from_secs(4));
+        assert_eq!(bot.time_left.get(), Some((Duration::from_secs(6), 0)));
+        bot.spend_time(Duration::from_secs(7));
+        assert_eq!(bot.time_left.get(), Some((Duration::from_secs(30), 5)));
+        bot.spend_time(Duration::from_secs(1));
+        assert_eq!(bot.time_left.get(), Some((Duration::from_secs(29), 4)));
+        bot.set_time_left(Duration::from_secs(2), 1);
+        bot.spend_time(Duration::from_secs(1));
+        assert_eq!(bot.time_left.get(), Some((Duration::from_secs(30), 5)));
+
+        // without a time limit the clock is not kept
+        bot.set_time_settings(Duration::ZERO, Duration::from_secs(1), 0);
+        bot.set_time_left(Duration::from_secs(30), 0);
+        assert_eq!(bot.time_left.get(), None);
+    }
+}
@@ -1,8 +1,36 @@
-use crate::board::Board;
-use crate::game::{Game, Move};
-use crate::players::player::Player;
+use crate::board::BoardSize;
+use crate::game::{Coordinates, Game, Move};
+use crate::handicap::fixed_handicap_coordinates;
+use crate::life;
+use crate::players::bot::Bot;
+use crate::players::player::{Action, DeadStonesResponse, Player};
+use crate::point::Point;
+use crate::sgf::writer;
 use crate::stones::{get_stone_name_from_stone, Stone};
 
+const HELP: &str = "Commands:
+  play D4      play a stone, D4 alone works too
+  pass         pass the turn
+  resign       resign the game
+  undo         take back your last move
+  hint         suggest a move
+  score        estimate the score of the position
+  save FILE    save the game to an SGF file
+  show         show the board
+  help         show this help";
+
+// What players can type at their turn
+#[derive(Debug, PartialEq)]
+enum Command {
+    Play(Move),
+    Undo,
+    Hint,
+    Score,
+    Save(String),
+    Show,
+    Help,
+}
+
 pub struct Human {
     pub stone: Stone,
     pub name: &'static str,
@@ -21,19 +49,60 @@ impl Human {
 }
 
 impl Human {
-    fn get_user_input() -> String {
+    // Next line typed by the player, None once the input is closed or cannot be read
+    fn get_user_input() -> Option<String> {
         let mut input = String::new();
         let stdin = std::io::stdin();
-        let choice_result = stdin.read_line(&mut input);
-        match choice_result {
-            Ok(_) => {
-                input
-            }
-            Err(_) => {
-                panic!("failed to read input");
-            }
+        match stdin.read_line(&mut input) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(input),
+        }
+    }
+
+    fn parse_command(input: &str, shape: BoardSize) -> Result<Command, String> {
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let point = |text: &str| {
+            Point::parse(text, shape)
+                .map(|point| Command::Play(Move::Play(point.into())))
+                .ok_or_else(|| format!("{} is not a point of the board, expected a point like D4", text))
+        };
+        match words.as_slice() {
+            [] => Err("Type a command, help to list them".to_string()),
+            ["play", vertex] => point(vertex),
+            ["play", ..] => Err("play needs a point, like play D4".to_string()),
+            ["pass"] => Ok(Command::Play(Move::Pass)),
+            ["resign"] => Ok(Command::Play(Move::Resign)),
+            ["undo"] => Ok(Command::Undo),
+            ["hint"] => Ok(Command::Hint),
+            ["score"] => Ok(Command::Score),
+            ["save"] => Err("save needs a file name, like save game.sgf".to_string()),
+            ["save", ..] => Ok(Command::Save(input.trim()["save".len()..].trim().to_string())),
+            ["show"] => Ok(Command::Show),
+            ["help"] => Ok(Command::Help),
+            // a lone word is only taken for a point like D4, so that a mistyped command is never played
+            [word] => Point::from_notation(word, shape)
+                .map(|point| Command::Play(Move::Play(point.into())))
+                .ok_or_else(|| format!("Unknown command: {}, help to list them", word)),
+            _ => Err(format!("Unknown command: {}, help to list them", input.trim())),
+        }
+    }
+
+    // Move the bot would play in the position
+    fn hint(&self, game: &Game) {
+        let suggestion = Bot::new(self.stone).choose_case(game);
+        match suggestion {
+            Move::Play(coords) => println!("Hint: {}", Point::from(coords).notation(game.shape())),
+            Move::Pass => println!("Hint: pass"),
+            Move::Resign => println!("Hint: resign"),
         }
+    }
 
+    // Result if play stopped now, with the dead stones estimated by playouts
+    fn estimate_score(game: &Game) {
+        let mut position = game.clone();
+        position.set_display(false);
+        position.mark_estimated_dead_stones(life::DEFAULT_PLAYOUTS);
+        println!("Estimated result: {}", position.scored_result());
     }
 }
 
@@ -42,24 +111,130 @@ impl Player for Human {
         self.stone
     }
 
+    fn choose_handicap_stones(&self, game: &Game, handicap: usize) -> Vec<Coordinates> {
+        let mut stones: Vec<Coordinates> = vec![];
+        while stones.len() < handicap {
+            println!("Place handicap stone {} of {}", stones.len() + 1, handicap);
+            let Some(input) = Self::get_user_input() else {
+                println!("Input closed, the handicap stones go on the star points");
+                return fixed_handicap_coordinates(game.shape(), handicap).unwrap_or_default();
+            };
+            match Point::parse(&input, game.shape()) {
+                Some(point) if !stones.contains(&point.into()) && game.get(point.x, point.y).is_none() => {
+                    stones.push(point.into())
+                }
+                _ => println!("Invalid handicap stone, expected a free point like D4"),
+            }
+        }
+        stones
+    }
+
+    fn propose_dead_stones(&self, game: &Game) -> Vec<Coordinates> {
+        println!("{}", game);
+        println!("{}, enter one stone of each dead chain, an empty line when done", self.name);
+        let mut dead_stones: Vec<Coordinates> = vec![];
+        // a closed input ends the proposal like an empty line
+        while let Some(user_input) = Self::get_user_input() {
+            if user_input.trim().is_empty() {
+                break;
+            }
+            let chain = Point::parse(&user_input, game.shape()).and_then(|point| game.chain_at(point.into()));
+            match chain {
+                Some(chain) => {
+                    for stone in chain.stones {
+                        if !dead_stones.contains(&stone) {
+                            dead_stones.push(stone);
+                        }
+                    }
+                }
+                None => println!("Invalid stone, expected a stone like D4"),
+            }
+        }
+        dead_stones
+    }
+
+    fn review_dead_stones(&self, game: &Game, proposal: &[Coordinates]) -> DeadStonesResponse {
+        println!("{}", game);
+        let proposal_points: Vec<String> = proposal
+            .iter()
+            .map(|coords| Point::from(*coords).notation(game.shape()))
+            .collect();
+        println!("Proposed dead stones: {}", proposal_points.join(" "));
+        loop {
+            println!("{}, do you accept? [y/n]", self.name);
+            let Some(input) = Self::get_user_input() else {
+                return DeadStonesResponse::Accept;
+            };
+            match input.trim() {
+                "y" | "yes" => return DeadStonesResponse::Accept,
+                "n" | "no" => return DeadStonesResponse::Dispute(self.propose_dead_stones(game)),
+                _ => continue,
+            }
+        }
+    }
+
     fn choose_case(&self, game: &Game) -> Move {
-        let mut user_input = Self::get_user_input();
-        let choice: Vec<&str> = user_input
-            .trim()
-            .split(' ').collect();
-        match choice.len() {
-            0 | 1 =>{
-                let res = None;
-                user_input.clear();
-                res
+        loop {
+            match self.choose_action(game) {
+                Action::Move(chosen_move) => return chosen_move,
+                Action::Undo => println!("No move can be taken back now"),
             }
-            2 => {
-                let choice_coords = choice.into_iter().map(|c| c.parse::<usize>().unwrap()).collect::<Vec<usize>>();
-                let res = Some((choice_coords[0], choice_coords[1]));
-                user_input.clear();
-                res
+        }
+    }
+
+    /*
+        Reads commands until one of them is a move or an undo, the others being answered on the way.
+        The player resigns when the input is closed, as nothing can be played any more
+    */
+    fn choose_action(&self, game: &Game) -> Action {
+        loop {
+            println!("{} to play, help for the commands:", self.name);
+            let Some(input) = Self::get_user_input() else {
+                println!("Input closed, {} resigns", self.name);
+                return Action::Move(Move::Resign);
+            };
+            match Self::parse_command(&input, game.shape()) {
+                Ok(Command::Play(chosen_move)) => return Action::Move(chosen_move),
+                Ok(Command::Undo) => return Action::Undo,
+                Ok(Command::Hint) => self.hint(game),
+                Ok(Command::Score) => Self::estimate_score(game),
+                Ok(Command::Save(path)) => match writer::save_game(game, &path) {
+                    Ok(()) => println!("Game saved to {}", path),
+                    Err(e) => println!("Could not save the game to {}: {}", path, e),
+                },
+                Ok(Command::Show) => println!("{}", game),
+                Ok(Command::Help) => println!("{}", HELP),
+                Err(message) => println!("{}", message),
             }
-            _ => panic!("Input is not of length 0 or 2")
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_recognised() {
+        assert_eq!(Human::parse_command("play D4", (19, 19)), Ok(Command::Play(Move::Play((15, 3)))));
+        assert_eq!(Human::parse_command("d4\n", (19, 19)), Ok(Command::Play(Move::Play((15, 3)))));
+        assert_eq!(Human::parse_command("play dp", (19, 19)), Ok(Command::Play(Move::Play((15, 3)))));
+        assert_eq!(Human::parse_command("  pass ", (19, 19)), Ok(Command::Play(Move::Pass)));
+        assert_eq!(Human::parse_command("resign", (19, 19)), Ok(Command::Play(Move::Resign)));
+        assert_eq!(Human::parse_command("undo", (19, 19)), Ok(Command::Undo));
+        assert_eq!(Human::parse_command("hint", (19, 19)), Ok(Command::Hint));
+        assert_eq!(Human::parse_command("score", (19, 19)), Ok(Command::Score));
+        assert_eq!(Human::parse_command("show", (19, 19)), Ok(Command::Show));
+        assert_eq!(Human::parse_command("help", (19, 19)), Ok(Command::Help));
+        assert_eq!(Human::parse_command("save my game.sgf\n", (19, 19)), Ok(Command::Save("my game.sgf".to_string())));
+    }
+
+    #[test]
+    fn mistyped_commands_are_not_played() {
+        // two letter words are SGF points, only taken as such after play
+        for input in ["no", "hi", "ok", "dp", "K10", "", "play", "play Z99", "save", "pass now"] {
+            assert!(Human::parse_command(input, (9, 9)).is_err(), "{} is not a command", input);
+        }
+        assert_eq!(Human::parse_command("no", (9, 9)), Err("Unknown command: no, help to list them".to_string()));
+    }
+}
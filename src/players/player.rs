@@ -1,8 +1,22 @@
 use crate::stones::{get_stone_name_from_stone, Stone};
 use std::fmt::{Display, Formatter, Result};
 use std::hash::Hash;
-use crate::board::Board;
-use crate::game::{Game, Move};
+use std::time::Duration;
+use crate::game::{Coordinates, Game, Move};
+use crate::handicap::fixed_handicap_coordinates;
+
+pub enum DeadStonesResponse {
+    Accept,
+    // counter proposal, listing every stone the player considers dead
+    Dispute(Vec<Coordinates>),
+}
+
+// What a player does on its turn: play a move or take back the last moves
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Move(Move),
+    Undo,
+}
 
 pub trait Player {
     fn get_stone(&self) -> Stone;
@@ -10,6 +24,30 @@ pub trait Player {
         get_stone_name_from_stone(self.get_stone())
     }
     fn choose_case(&self, game: &Game) -> Move;
+    // Players that can take moves back override this, by default they always play
+    fn choose_action(&self, game: &Game) -> Action {
+        Action::Move(self.choose_case(game))
+    }
+    // Win rate the player estimated when choosing its last move, if it evaluates positions
+    fn last_evaluation(&self) -> Option<f32> {
+        None
+    }
+    // Time control of the game, only relevant to players that think within a time budget
+    fn set_time_settings(&self, _main_time: Duration, _byo_yomi_time: Duration, _byo_yomi_stones: u32) {}
+    // Time left on the player's clock, with the stones to play in it during byo-yomi and none in main time
+    fn set_time_left(&self, _time_left: Duration, _stones: u32) {}
+    // Where to put the stones of a free handicap, star points by default
+    fn choose_handicap_stones(&self, game: &Game, handicap: usize) -> Vec<Coordinates> {
+        fixed_handicap_coordinates(game.shape(), handicap).unwrap_or_default()
+    }
+    // Stones the player considers dead once both players passed, none by default
+    fn propose_dead_stones(&self, _game: &Game) -> Vec<Coordinates> {
+        vec![]
+    }
+    // Answer to the opponent's dead stones proposal, accepted by default
+    fn review_dead_stones(&self, _game: &Game, _proposal: &[Coordinates]) -> DeadStonesResponse {
+        DeadStonesResponse::Accept
+    }
 }
 
 impl Display for dyn Player {
@@ -0,0 +1,157 @@
+use crate::board::{BoardSize, MAX_SIDE};
+use crate::game::Coordinates;
+
+// Column letters of the usual notation, I is skipped so that it is not mistaken for J or 1
+pub const COLUMNS: &str = "ABCDEFGHJKLMNOPQRSTUVWXYZ";
+
+/*
+    Point of the board, with its names in the notations players and files use.
+    The usual notation, also used for GTP vertices, names the column from the left with a letter and the row
+    from the bottom with a number, like D4: it depends on the board height.
+    SGF names the column then the row from the top with a letter each, lower case up to 26 lines and upper case beyond
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Point {
+    // row from the top
+    pub x: usize,
+    // column from the left
+    pub y: usize,
+}
+
+impl Point {
+    pub fn new(x: usize, y: usize) -> Point {
+        Point { x, y }
+    }
+
+    // Point written like D4 or d4 on a board of the given shape, the row in plain digits without leading zero
+    pub fn from_notation(text: &str, (height, width): BoardSize) -> Option<Point> {
+        let text = text.trim().to_uppercase();
+        let mut chars = text.chars();
+        let column = COLUMNS.find(chars.next()?)?;
+        let digits = chars.as_str();
+        if digits.starts_with('0') || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        let row = digits.parse::<usize>().ok()?;
+        if column >= width || row == 0 || row > height {
+            return None;
+        }
+        Some(Point::new(height - row, column))
+    }
+
+    pub fn notation(&self, (height, _): BoardSize) -> String {
+        format!("{}{}", Self::column_letter(self.y), height - self.x)
+    }
+
+    pub fn column_letter(column: usize) -> char {
+        COLUMNS.as_bytes()[column] as char
+    }
+
+    // Point written like dp, None if the letters are not those of lines of the largest board
+    pub fn from_sgf(text: &str) -> Option<Point> {
+        let mut letters = text.chars();
+        let (column, row) = (letters.next()?, letters.next()?);
+        if letters.next().is_some() {
+            return None;
+        }
+        let (x, y) = (Self::sgf_line(row)?, Self::sgf_line(column)?);
+        if x < MAX_SIDE && y < MAX_SIDE {
+            Some(Point::new(x, y))
+        } else {
+            None
+        }
+    }
+
+    pub fn to_sgf(&self) -> String {
+        format!("{}{}", Self::sgf_letter(self.y), Self::sgf_letter(self.x))
+    }
+
+    fn sgf_letter(line: usize) -> char {
+        if line < 26 {
+            (b'a' + line as u8) as char
+        } else {
+            (b'A' + (line - 26) as u8) as char
+        }
+    }
+
+    fn sgf_line(letter: char) -> Option<usize> {
+        match letter {
+            'a'..='z' => Some(letter as usize - 'a' as usize),
+            'A'..='Z' => Some(letter as usize - 'A' as usize + 26),
+            _ => None,
+        }
+    }
+
+    // Point of the board in either notation, D4 being tried before dp
+    pub fn parse(text: &str, shape: BoardSize) -> Option<Point> {
+        let text = text.trim();
+        Self::from_notation(text, shape)
+            .or_else(|| Self::from_sgf(text).filter(|point| point.x < shape.0 && point.y < shape.1))
+    }
+}
+
+impl From<Coordinates> for Point {
+    fn from((x, y): Coordinates) -> Self {
+        Point::new(x, y)
+    }
+}
+
+impl From<Point> for Coordinates {
+    fn from(point: Point) -> Self {
+        (point.x, point.y)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notation_skips_i_and_counts_rows_from_the_bottom() {
+        assert_eq!(Point::from_notation("A1", (19, 19)), Some(Point::new(18, 0)));
+        assert_eq!(Point::from_notation("A19", (19, 19)), Some(Point::new(0, 0)));
+        assert_eq!(Point::from_notation("d4", (19, 19)), Some(Point::new(15, 3)));
+        assert_eq!(Point::from_notation("J10", (19, 19)), Some(Point::new(9, 8)));
+        assert_eq!(Point::from_notation("I10", (19, 19)), None);
+        assert_eq!(Point::new(9, 8).notation((19, 19)), "J10");
+        assert_eq!(Point::new(2, 2).notation((9, 9)), "C7");
+    }
+
+    #[test]
+    fn notation_on_rectangular_boards() {
+        // 9 rows of 13 columns
+        assert_eq!(Point::from_notation("N1", (9, 13)), Some(Point::new(8, 12)));
+        assert_eq!(Point::from_notation("A9", (9, 13)), Some(Point::new(0, 0)));
+        assert_eq!(Point::from_notation("O1", (9, 13)), None);
+        assert_eq!(Point::from_notation("A10", (9, 13)), None);
+        assert_eq!(Point::new(0, 12).notation((9, 13)), "N9");
+    }
+
+    #[test]
+    fn notation_refuses_malformed_rows() {
+        for text in ["D+4", "D04", "D0", "D", "D-1", "D 4", "D4.0", "", "4D", "Z4", "DD"] {
+            assert_eq!(Point::from_notation(text, (19, 19)), None, "{} is not a point", text);
+        }
+        assert_eq!(Point::from_notation(" D4 ", (19, 19)), Some(Point::new(15, 3)));
+    }
+
+    #[test]
+    fn sgf_letters() {
+        assert_eq!(Point::from_sgf("dp"), Some(Point::new(15, 3)));
+        assert_eq!(Point::new(15, 3).to_sgf(), "dp");
+        assert_eq!(Point::from_sgf("yy"), Some(Point::new(24, 24)));
+        for text in ["d", "dpp", "d1", "zz", "Aa", ""] {
+            assert_eq!(Point::from_sgf(text), None, "{} is not a point", text);
+        }
+    }
+
+    #[test]
+    fn parse_takes_either_notation_on_the_board() {
+        assert_eq!(Point::parse("D4", (19, 19)), Some(Point::new(15, 3)));
+        assert_eq!(Point::parse("dd", (19, 19)), Some(Point::new(3, 3)));
+        assert_eq!(Point::parse("ss", (19, 19)), Some(Point::new(18, 18)));
+        assert_eq!(Point::parse("ss", (9, 9)), None);
+        assert_eq!(Point::parse("K10", (9, 9)), None);
+    }
+}
@@ -0,0 +1,125 @@
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum KoRule {
+    /// A move may not recreate the position as it was before the opponent's last move
+    #[default]
+    Simple,
+    /// A move may not recreate any earlier board position
+    PositionalSuperko,
+    /// A move may not recreate an earlier board position with the same player to move
+    SituationalSuperko,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Scoring {
+    /// Stones on the board plus surrounded empty points, as in Chinese rules
+    #[default]
+    Area,
+    /// Surrounded empty points plus captured stones, as in Japanese and Korean rules
+    Territory,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum HandicapCompensation {
+    /// White receives nothing for the handicap stones
+    #[default]
+    None,
+    /// White receives one point for each handicap stone
+    Full,
+    /// White receives one point for each handicap stone after the first
+    MinusOne,
+}
+
+impl HandicapCompensation {
+    pub fn points(&self, handicap: usize) -> f32 {
+        match self {
+            HandicapCompensation::None => 0.0,
+            HandicapCompensation::Full => handicap as f32,
+            HandicapCompensation::MinusOne => handicap.saturating_sub(1) as f32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rules {
+    pub name: &'static str,
+    pub scoring: Scoring,
+    pub ko_rule: KoRule,
+    pub suicide_allowed: bool,
+    pub handicap_compensation: HandicapCompensation,
+    pub komi: f32,
+}
+
+impl Rules {
+    pub fn chinese() -> Rules {
+        Rules {
+            name: "Chinese",
+            scoring: Scoring::Area,
+            ko_rule: KoRule::PositionalSuperko,
+            suicide_allowed: false,
+            handicap_compensation: HandicapCompensation::Full,
+            komi: 7.5,
+        }
+    }
+
+    pub fn japanese() -> Rules {
+        Rules {
+            name: "Japanese",
+            scoring: Scoring::Territory,
+            ko_rule: KoRule::Simple,
+            suicide_allowed: false,
+            handicap_compensation: HandicapCompensation::None,
+            komi: 6.5,
+        }
+    }
+
+    pub fn aga() -> Rules {
+        Rules {
+            name: "AGA",
+            scoring: Scoring::Area,
+            ko_rule: KoRule::SituationalSuperko,
+            suicide_allowed: false,
+            handicap_compensation: HandicapCompensation::MinusOne,
+            komi: 7.5,
+        }
+    }
+
+    pub fn tromp_taylor() -> Rules {
+        Rules {
+            name: "Tromp-Taylor",
+            scoring: Scoring::Area,
+            ko_rule: KoRule::PositionalSuperko,
+            suicide_allowed: true,
+            handicap_compensation: HandicapCompensation::None,
+            komi: 7.5,
+        }
+    }
+
+    pub fn new_zealand() -> Rules {
+        Rules {
+            name: "NZ",
+            scoring: Scoring::Area,
+            ko_rule: KoRule::SituationalSuperko,
+            suicide_allowed: true,
+            handicap_compensation: HandicapCompensation::None,
+            komi: 7.0,
+        }
+    }
+
+    // Finds a preset from its name, as written in SGF RU properties or GTP rule commands
+    pub fn from_name(name: &str) -> Option<Rules> {
+        match name.trim().to_lowercase().as_str() {
+            "chinese" | "cn" => Some(Self::chinese()),
+            "japanese" | "jp" | "korean" => Some(Self::japanese()),
+            "aga" => Some(Self::aga()),
+            "tromp-taylor" | "tromp_taylor" | "tt" => Some(Self::tromp_taylor()),
+            "nz" | "new zealand" | "new_zealand" => Some(Self::new_zealand()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::chinese()
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+use crate::board::{Board, BoardSize, DEFAULT_SIDE};
+use crate::game::{Coordinates, GameResult, Move};
+use crate::point::Point;
+use crate::rules::{HandicapCompensation, KoRule, Rules, Scoring};
+use crate::sgf::parser::SgfNode;
+use crate::stones::{Stone, BLACK_STONE, WHITE_STONE};
+
+pub mod parser;
+pub mod reader;
+pub mod tree;
+pub mod writer;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SgfError {
+    /// The text is not valid SGF, at the given byte offset
+    Syntax { position: usize, message: String },
+    /// A property value cannot be understood
+    InvalidProperty { property: String, value: String },
+    /// A move of the main line is illegal, numbered from 1
+    IllegalMove { move_number: usize, reason: String },
+    /// Setup properties or the handicap cannot be applied
+    InvalidSetup(String),
+    Io(String),
+}
+
+impl fmt::Display for SgfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SgfError::Syntax { position, message } => write!(f, "SGF syntax error at byte {}: {}", position, message),
+            SgfError::InvalidProperty { property, value } => write!(f, "Invalid value [{}] for SGF property {}", value, property),
+            SgfError::IllegalMove { move_number, reason } => write!(f, "Illegal move {}: {}", move_number, reason),
+            SgfError::InvalidSetup(reason) => write!(f, "Invalid setup: {}", reason),
+            SgfError::Io(reason) => write!(f, "Cannot access the SGF file: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SgfError {}
+
+pub(crate) fn invalid_property(property: &str, value: &str) -> SgfError {
+    SgfError::InvalidProperty { property: property.to_string(), value: value.to_string() }
+}
+
+pub fn color(stone: Stone) -> &'static str {
+    if stone == BLACK_STONE {
+        "B"
+    } else {
+        "W"
+    }
+}
+
+// Line added to move comments with the win rate of the move, for viewers that do not read SBKV
+pub fn win_rate_comment(stone: Stone, win_rate: f32) -> String {
+    format!("{} win rate: {:.1}%", color(stone), 100.0 * win_rate)
+}
+
+// Comment without its win rate line, None if nothing else is left
+pub fn strip_win_rate_comment(comment: &str) -> Option<&str> {
+    let (text, last_line) = comment.rsplit_once('\n').unwrap_or(("", comment));
+    let is_win_rate_line = (last_line.starts_with("B win rate: ") || last_line.starts_with("W win rate: "))
+        && last_line.ends_with('%');
+    let text = if is_win_rate_line { text } else { comment };
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+// Escapes the characters closing or escaping an SGF text value
+pub fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == ']' || c == '\\' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Move of a B or W property, an empty value or tt on boards up to 19x19 being a pass
+pub fn parse_move(value: &str, (height, width): (usize, usize)) -> Option<Move> {
+    let value = value.trim();
+    if value.is_empty() || (value == "tt" && height <= 19 && width <= 19) {
+        return Some(Move::Pass);
+    }
+    Point::from_sgf(value).map(|point| Move::Play(point.into()))
+}
+
+// Points of a list of points, where aa:cc stands for the rectangle between both corners
+pub fn parse_point_list(values: &[String]) -> Option<Vec<Coordinates>> {
+    let mut points: Vec<Coordinates> = vec![];
+    for value in values {
+        match value.split_once(':') {
+            Some((first, second)) => {
+                let (first, second) = (Point::from_sgf(first)?, Point::from_sgf(second)?);
+                for x in first.x.min(second.x)..=first.x.max(second.x) {
+                    for y in first.y.min(second.y)..=first.y.max(second.y) {
+                        points.push((x, y));
+                    }
+                }
+            }
+            None => points.push(Point::from_sgf(value)?.into()),
+        }
+    }
+    Some(points)
+}
+
+// SZ is either the side of a square board or columns:rows
+pub fn parse_size(node: &SgfNode) -> Result<BoardSize, SgfError> {
+    let Some(value) = node.value("SZ") else {
+        return Ok((DEFAULT_SIDE, DEFAULT_SIDE));
+    };
+    let sides: Vec<Option<usize>> = value.split(':').map(|side| side.trim().parse::<usize>().ok()).collect();
+    let shape = match sides.as_slice() {
+        [Some(side)] => (*side, *side),
+        [Some(width), Some(height)] => (*height, *width),
+        _ => return Err(invalid_property("SZ", value)),
+    };
+    if Board::is_valid_shape(shape) {
+        Ok(shape)
+    } else {
+        Err(invalid_property("SZ", value))
+    }
+}
+
+pub fn parse_color(value: &str) -> Option<Stone> {
+    match value.trim() {
+        "B" | "b" => Some(BLACK_STONE),
+        "W" | "w" => Some(WHITE_STONE),
+        _ => None,
+    }
+}
+
+// Result written like B+R, W+3.5 or 0, None for results the game cannot hold, like a win on time
+pub fn parse_result(value: &str) -> Option<GameResult> {
+    let value = value.trim();
+    if value == "0" || value == "Draw" {
+        return Some(GameResult::Draw);
+    }
+    let (winner, margin) = value.split_once('+')?;
+    let winner = parse_color(winner)?;
+    match margin {
+        "R" | "Resign" => Some(GameResult::Resignation(winner)),
+        _ => margin.parse::<f32>().ok().map(|margin| GameResult::Score(winner, margin)),
+    }
+}
+
+/*
+    RU value of the rules: the name of the preset, followed by the settings that differ from it,
+    like Chinese:ko=situational,suicide=yes, so that customized rules read back the same. Komi is in KM
+*/
+pub fn rules_property(rules: &Rules) -> String {
+    let preset = Rules::from_name(rules.name).unwrap_or_default();
+    let mut settings: Vec<String> = vec![];
+    if rules.scoring != preset.scoring {
+        let scoring = match rules.scoring {
+            Scoring::Area => "area",
+            Scoring::Territory => "territory",
+        };
+        settings.push(format!("scoring={}", scoring));
+    }
+    if rules.ko_rule != preset.ko_rule {
+        let ko_rule = match rules.ko_rule {
+            KoRule::Simple => "simple",
+            KoRule::PositionalSuperko => "positional",
+            KoRule::SituationalSuperko => "situational",
+        };
+        settings.push(format!("ko={}", ko_rule));
+    }
+    if rules.suicide_allowed != preset.suicide_allowed {
+        settings.push(format!("suicide={}", if rules.suicide_allowed { "yes" } else { "no" }));
+    }
+    if rules.handicap_compensation != preset.handicap_compensation {
+        let compensation = match rules.handicap_compensation {
+            HandicapCompensation::None => "none",
+            HandicapCompensation::Full => "full",
+            HandicapCompensation::MinusOne => "minus-one",
+        };
+        settings.push(format!("compensation={}", compensation));
+    }
+    if settings.is_empty() {
+        rules.name.to_string()
+    } else {
+        format!("{}:{}", rules.name, settings.join(","))
+    }
+}
+
+// Rules of an RU value written by rules_property, None if the preset or a setting is unknown
+pub fn parse_rules(value: &str) -> Option<Rules> {
+    let (name, settings) = value.split_once(':').unwrap_or((value, ""));
+    let mut rules = Rules::from_name(name)?;
+    for setting in settings.split(',').map(str::trim).filter(|setting| !setting.is_empty()) {
+        match setting.split_once('=')? {
+            ("scoring", "area") => rules.scoring = Scoring::Area,
+            ("scoring", "territory") => rules.scoring = Scoring::Territory,
+            ("ko", "simple") => rules.ko_rule = KoRule::Simple,
+            ("ko", "positional") => rules.ko_rule = KoRule::PositionalSuperko,
+            ("ko", "situational") => rules.ko_rule = KoRule::SituationalSuperko,
+            ("suicide", "yes") => rules.suicide_allowed = true,
+            ("suicide", "no") => rules.suicide_allowed = false,
+            ("compensation", "none") => rules.handicap_compensation = HandicapCompensation::None,
+            ("compensation", "full") => rules.handicap_compensation = HandicapCompensation::Full,
+            ("compensation", "minus-one") => rules.handicap_compensation = HandicapCompensation::MinusOne,
+            _ => return None,
+        }
+    }
+    Some(rules)
+}
@@ -0,0 +1,206 @@
+use std::iter::Peekable;
+use std::str::CharIndices;
+use crate::sgf::SgfError;
+
+// Properties of a node, in the order they were read, each with its values
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SgfNode {
+    pub properties: Vec<(String, Vec<String>)>,
+}
+
+impl SgfNode {
+    pub fn get(&self, ident: &str) -> Option<&[String]> {
+        self.properties
+            .iter()
+            .find(|(property, _)| property == ident)
+            .map(|(_, values)| values.as_slice())
+    }
+
+    // First value of the property
+    pub fn value(&self, ident: &str) -> Option<&str> {
+        self.get(ident).and_then(|values| values.first()).map(|value| value.as_str())
+    }
+}
+
+// A sequence of nodes followed by the variations branching from its last node, the first one being the main line
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SgfTree {
+    pub nodes: Vec<SgfNode>,
+    pub variations: Vec<SgfTree>,
+}
+
+impl SgfTree {
+    pub fn main_line(&self) -> Vec<&SgfNode> {
+        let mut nodes: Vec<&SgfNode> = vec![];
+        let mut tree = self;
+        loop {
+            nodes.extend(tree.nodes.iter());
+            match tree.variations.first() {
+                Some(variation) => tree = variation,
+                None => return nodes,
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn position(&mut self) -> usize {
+        self.chars.peek().map_or(self.text.len(), |(position, _)| *position)
+    }
+
+    fn error(&mut self, message: &str) -> SgfError {
+        SgfError::Syntax { position: self.position(), message: message.to_string() }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SgfError> {
+        self.skip_whitespace();
+        match self.chars.next_if(|(_, c)| *c == expected) {
+            Some(_) => Ok(()),
+            None => Err(self.error(&format!("expected '{}'", expected))),
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.peek().map(|(_, c)| *c)
+    }
+
+    fn tree(&mut self) -> Result<SgfTree, SgfError> {
+        self.expect('(')?;
+        let mut tree = SgfTree::default();
+        while self.peek() == Some(';') {
+            tree.nodes.push(self.node()?);
+        }
+        if tree.nodes.is_empty() {
+            return Err(self.error("a game tree needs at least one node"));
+        }
+        while self.peek() == Some('(') {
+            tree.variations.push(self.tree()?);
+        }
+        self.expect(')')?;
+        Ok(tree)
+    }
+
+    fn node(&mut self) -> Result<SgfNode, SgfError> {
+        self.expect(';')?;
+        let mut node = SgfNode::default();
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            // lower case letters of old FF[3] identifiers like AddBlack are ignored
+            let mut ident = String::new();
+            while let Some((_, c)) = self.chars.next_if(|(_, c)| c.is_ascii_alphabetic()) {
+                if c.is_ascii_uppercase() {
+                    ident.push(c);
+                }
+            }
+            let mut values: Vec<String> = vec![];
+            while self.peek() == Some('[') {
+                values.push(self.value()?);
+            }
+            if ident.is_empty() || values.is_empty() {
+                return Err(self.error("expected a property identifier followed by values"));
+            }
+            match node.properties.iter_mut().find(|(property, _)| *property == ident) {
+                Some((_, existing_values)) => existing_values.extend(values),
+                None => node.properties.push((ident, values)),
+            }
+        }
+        Ok(node)
+    }
+
+    fn value(&mut self) -> Result<String, SgfError> {
+        self.expect('[')?;
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, ']')) => return Ok(value),
+                Some((_, '\\')) => match self.chars.next() {
+                    // an escaped line break is a soft line break and disappears
+                    Some((_, '\n')) => {
+                        self.chars.next_if(|(_, c)| *c == '\r');
+                    }
+                    Some((_, '\r')) => {
+                        self.chars.next_if(|(_, c)| *c == '\n');
+                    }
+                    Some((_, c)) => value.push(c),
+                    None => return Err(self.error("unterminated property value")),
+                },
+                Some((_, c)) => value.push(c),
+                None => return Err(self.error("unterminated property value")),
+            }
+        }
+    }
+}
+
+// Every game tree of an SGF collection
+pub fn parse(text: &str) -> Result<Vec<SgfTree>, SgfError> {
+    let mut parser = Parser { text, chars: text.char_indices().peekable() };
+    let mut trees: Vec<SgfTree> = vec![];
+    while parser.peek().is_some() {
+        trees.push(parser.tree()?);
+    }
+    if trees.is_empty() {
+        return Err(parser.error("no game tree found"));
+    }
+    Ok(trees)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn syntax_error_position(text: &str) -> Option<usize> {
+        match parse(text) {
+            Err(SgfError::Syntax { position, .. }) => Some(position),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn escapes_and_soft_line_breaks() {
+        let trees = parse("(;C[a \\] b \\\\ c\\\nd]GN[x\\:y])").unwrap();
+        let root = &trees[0].nodes[0];
+        assert_eq!(root.value("C"), Some("a ] b \\ cd"));
+        assert_eq!(root.value("GN"), Some("x:y"));
+    }
+
+    #[test]
+    fn values_and_identifiers() {
+        let trees = parse("(;AB[aa][bb] AddWhite[cc];B[dd]AB[ee])").unwrap();
+        let root = &trees[0].nodes[0];
+        assert_eq!(root.get("AB").unwrap(), ["aa", "bb"]);
+        // the lower case letters of FF[3] identifiers are dropped
+        assert_eq!(root.value("AW"), Some("cc"));
+        assert_eq!(trees[0].nodes[1].get("AB").unwrap(), ["ee"]);
+    }
+
+    #[test]
+    fn variations_and_main_line() {
+        let trees = parse("(;SZ[9];B[aa](;W[bb];B[cc])(;W[dd]))(;SZ[13])").unwrap();
+        assert_eq!(trees.len(), 2);
+        assert_eq!(trees[0].variations.len(), 2);
+        assert_eq!(trees[0].variations[1].nodes[0].value("W"), Some("dd"));
+        let main_line: Vec<Option<&str>> =
+            trees[0].main_line().iter().map(|node| node.value("B").or(node.value("W"))).collect();
+        assert_eq!(main_line, vec![None, Some("aa"), Some("bb"), Some("cc")]);
+    }
+
+    #[test]
+    fn malformed_input() {
+        assert_eq!(syntax_error_position(""), Some(0));
+        assert_eq!(syntax_error_position("(;B[aa]"), Some(7));
+        assert_eq!(syntax_error_position("(;C[no end"), Some(10));
+        assert_eq!(syntax_error_position("()"), Some(1));
+        assert_eq!(syntax_error_position("(;B)"), Some(3));
+        assert_eq!(syntax_error_position(";B[aa]"), Some(0));
+        assert_eq!(syntax_error_position("(;B[aa]) junk"), Some(9));
+    }
+}
@@ -0,0 +1,224 @@
+use std::fs;
+use std::rc::Rc;
+use log::warn;
+use crate::game::{Coordinates, Game, GameResult, Move};
+use crate::players::player::Player;
+use crate::rules::Rules;
+use crate::sgf::parser::{parse, SgfNode, SgfTree};
+use crate::sgf::{invalid_property, parse_color, parse_move, parse_point_list, parse_result, parse_rules, parse_size, strip_win_rate_comment, SgfError};
+use crate::signals::Signals;
+use crate::stones::{Stone, BLACK_STONE, EMPTY, WHITE_STONE};
+
+fn parse_number<T: std::str::FromStr>(node: &SgfNode, property: &str) -> Result<Option<T>, SgfError> {
+    match node.value(property) {
+        Some(value) => value.trim().parse::<T>().map(Some).map_err(|_| invalid_property(property, value)),
+        None => Ok(None),
+    }
+}
+
+fn setup_points(node: &SgfNode, property: &str) -> Result<Vec<Coordinates>, SgfError> {
+    match node.get(property) {
+        Some(values) => parse_point_list(values).ok_or_else(|| invalid_property(property, &values.join("]["))),
+        None => Ok(vec![]),
+    }
+}
+
+/*
+    Applies AB, AW, AE and PL, the handicap stones of the root node going through the handicap placement.
+    Setup after the first move is recorded along with the moves, which stay in the game
+*/
+fn apply_setup(game: &mut Game, node: &SgfNode, handicap: usize) -> Result<(), SgfError> {
+    let mut black = setup_points(node, "AB")?;
+    let white = setup_points(node, "AW")?;
+    let empty = setup_points(node, "AE")?;
+    let to_play = match node.value("PL") {
+        Some(value) => Some(parse_color(value).ok_or_else(|| invalid_property("PL", value))?),
+        None => None,
+    };
+    if handicap >= 2 {
+        let placement = if black.is_empty() {
+            game.set_fixed_handicap(handicap).map(|_| ())
+        } else {
+            game.set_free_handicap(&black)
+        };
+        placement.map_err(|e| SgfError::InvalidSetup(e.to_string()))?;
+        black.clear();
+    }
+    if black.is_empty() && white.is_empty() && empty.is_empty() && to_play.is_none() {
+        return Ok(());
+    }
+    let mut stones: Vec<(Coordinates, Stone)> = vec![];
+    stones.extend(empty.into_iter().map(|coords| (coords, EMPTY)));
+    stones.extend(black.into_iter().map(|coords| (coords, BLACK_STONE)));
+    stones.extend(white.into_iter().map(|coords| (coords, WHITE_STONE)));
+    let to_play = to_play.unwrap_or(game.get_current_player().get_stone());
+    game.add_setup(&stones, to_play).map_err(|e| SgfError::InvalidSetup(e.to_string()))
+}
+
+/*
+    Plays the B or W move of the node, with its comment and evaluation.
+    SGF lets a player move twice in a row, as in problems where the setup does not say who plays:
+    the opponent is then taken to have passed in between
+*/
+fn apply_move(game: &mut Game, node: &SgfNode, move_number: &mut usize) -> Result<(), SgfError> {
+    for (property, stone) in [("B", BLACK_STONE), ("W", WHITE_STONE)] {
+        let Some(value) = node.value(property) else {
+            continue;
+        };
+        let played_move = parse_move(value, game.shape()).ok_or_else(|| invalid_property(property, value))?;
+        *move_number += 1;
+        let illegal_move = |reason: String| SgfError::IllegalMove { move_number: *move_number, reason };
+        // play goes on after a double pass when the players disagreed on dead stones
+        if game.is_over() {
+            game.resume_play();
+        }
+        if game.get_current_player().get_stone() != stone {
+            match game.step(Move::Pass) {
+                Ok(()) | Err(Signals::DoublePass) => {}
+                Err(e) => return Err(illegal_move(e.to_string())),
+            }
+            if game.is_over() {
+                game.resume_play();
+            }
+        }
+        match game.step(played_move) {
+            Ok(()) | Err(Signals::DoublePass) => {}
+            Err(e) => return Err(illegal_move(e.to_string())),
+        }
+        apply_annotations(game, node, stone);
+    }
+    Ok(())
+}
+
+// Comment and SBKV evaluation of the node, given to the last move played by the stone
+fn apply_annotations(game: &mut Game, node: &SgfNode, stone: Stone) {
+    // an evaluation the reader does not understand is not worth losing the game for
+    let black_win_rate = parse_number::<f32>(node, "SBKV").unwrap_or_else(|e| {
+        warn!("{}, the evaluation is ignored", e);
+        None
+    });
+    if let Some(black_win_rate) = black_win_rate {
+        let black_win_rate = black_win_rate / 100.0;
+        game.set_last_evaluation(if stone == BLACK_STONE { black_win_rate } else { 1.0 - black_win_rate });
+    }
+    let comment = match black_win_rate {
+        // the win rate written in the comment along with SBKV is not part of the comment
+        Some(_) => node.value("C").and_then(strip_win_rate_comment),
+        None => node.value("C"),
+    };
+    if let Some(comment) = comment {
+        game.set_last_comment(comment);
+    }
+}
+
+/*
+    Builds the game of the main line of the tree: board size, rules and komi from the root node,
+    then every setup property and move in order, and the result of the root node.
+    The game can be continued or analysed, unless the main line ends with a double pass or has a result
+*/
+pub fn game_from_tree(tree: &SgfTree, players: [Rc<Box<dyn Player>>; 2], display: bool) -> Result<Game, SgfError> {
+    let nodes = tree.main_line();
+    let root = nodes[0];
+    if let Some(game_type) = root.value("GM") {
+        if game_type.trim() != "1" {
+            return Err(invalid_property("GM", game_type));
+        }
+    }
+    let shape = parse_size(root)?;
+    let mut rules = match root.value("RU") {
+        Some(name) => parse_rules(name).unwrap_or_else(|| {
+            warn!("Unknown rules [{}], the default rules are used", name);
+            Rules::default()
+        }),
+        None => Rules::default(),
+    };
+    if let Some(komi) = parse_number::<f32>(root, "KM")? {
+        rules.komi = komi;
+    }
+    let handicap = parse_number::<usize>(root, "HA")?.unwrap_or(0);
+
+    let mut game = Game::new(players, shape, false, rules).map_err(|e| SgfError::InvalidSetup(e.to_string()))?;
+    let mut move_number = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        apply_setup(&mut game, node, if i == 0 { handicap } else { 0 })?;
+        apply_move(&mut game, node, &mut move_number)?;
+    }
+    if let Some(value) = root.value("RE") {
+        let to_play = game.get_current_player().get_stone();
+        match parse_result(value) {
+            // the resignation of the player to move is played, with the comment of a last node without move
+            Some(GameResult::Resignation(winner)) if !game.is_over() && winner != to_play => {
+                let _ = game.step(Move::Resign);
+                let last_node = nodes[nodes.len() - 1];
+                if nodes.len() > 1 && last_node.value("B").is_none() && last_node.value("W").is_none() {
+                    apply_annotations(&mut game, last_node, to_play);
+                }
+            }
+            Some(result) => game.set_result(result),
+            None => warn!("Result [{}] cannot be kept, the game is left unfinished", value),
+        }
+    }
+    game.set_display(display);
+    Ok(game)
+}
+
+// Game of the first tree of an SGF collection
+pub fn read_game(text: &str, players: [Rc<Box<dyn Player>>; 2], display: bool) -> Result<Game, SgfError> {
+    let trees = parse(text)?;
+    game_from_tree(&trees[0], players, display)
+}
+
+pub fn load_game(path: &str, players: [Rc<Box<dyn Player>>; 2], display: bool) -> Result<Game, SgfError> {
+    let text = fs::read_to_string(path).map_err(|e| SgfError::Io(e.to_string()))?;
+    read_game(&text, players, display)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sgf::writer::write_game;
+    use crate::test_support::players;
+
+    #[test]
+    fn moves_in_a_row_by_one_color_get_a_pass_in_between() {
+        let game = read_game("(;SZ[9];B[cc];B[dd];W[ee])", players(), false).unwrap();
+        let moves: Vec<Move> = game.moves().collect();
+        assert_eq!(moves, vec![Move::Play((2, 2)), Move::Pass, Move::Play((3, 3)), Move::Play((4, 4))]);
+
+        // a problem where White moves first without PL
+        let game = read_game("(;SZ[9]AB[aa][bb];W[cc])", players(), false).unwrap();
+        assert_eq!(game.moves().collect::<Vec<Move>>(), vec![Move::Pass, Move::Play((2, 2))]);
+    }
+
+    #[test]
+    fn unknown_values_keep_the_defaults() {
+        let game = read_game("(;SZ[9]RU[Martian]KM[3.5];B[cc]SBKV[high])", players(), false).unwrap();
+        assert_eq!(game.rules().name, Rules::default().name);
+        assert_eq!(game.komi(), 3.5);
+        assert_eq!(game.last_record().unwrap().evaluation, None);
+        assert_eq!(game.move_number(), 1);
+    }
+
+    #[test]
+    fn setup_after_the_first_move_keeps_the_moves() {
+        let mut game = read_game("(;SZ[9];B[cc];W[dd];AB[ee]AE[dd]PL[W];W[ff])", players(), false).unwrap();
+        assert_eq!(game.move_number(), 3);
+        assert_eq!(game.get_board()[(4, 4)], BLACK_STONE);
+        assert_eq!(game.get_board()[(3, 3)], EMPTY);
+
+        let written = write_game(&game);
+        let read_again = read_game(&written, players(), false).unwrap();
+        assert_eq!(read_again.position_hash(), game.position_hash());
+        assert_eq!(read_again.setups(), game.setups());
+
+        // undo replays the setup, which then goes away with the move it followed
+        assert_eq!(game.undo(), Some(Move::Play((5, 5))));
+        assert_eq!(game.get_board()[(4, 4)], BLACK_STONE);
+        assert_eq!(game.get_board()[(3, 3)], EMPTY);
+        assert_eq!(game.get_current_player().get_stone(), WHITE_STONE);
+        assert_eq!(game.undo(), Some(Move::Play((3, 3))));
+        assert_eq!(game.get_board()[(4, 4)], EMPTY);
+        assert!(game.setups().is_empty());
+    }
+}
@@ -0,0 +1,485 @@
+use std::fmt::Write;
+use std::rc::Rc;
+use crate::board::BoardSize;
+use crate::game::{Coordinates, Game, GameResult, Move};
+use crate::players::player::Player;
+use crate::point::Point;
+use crate::sgf::parser::{parse, SgfNode, SgfTree};
+use crate::sgf::reader::game_from_tree;
+use crate::sgf::writer::write_game;
+use crate::sgf::{color, escape_text, invalid_property, parse_move, parse_point_list, parse_size, SgfError};
+use crate::stones::{Stone, BLACK_STONE, WHITE_STONE};
+
+// Marks drawn on the board at a node
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Markup {
+    pub labels: Vec<(Coordinates, String)>,
+    pub triangles: Vec<Coordinates>,
+    pub squares: Vec<Coordinates>,
+    pub circles: Vec<Coordinates>,
+}
+
+impl Markup {
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.triangles.is_empty() && self.squares.is_empty() && self.circles.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeNode {
+    // color and move played at the node, None for the root and setup nodes
+    pub played: Option<(Stone, Move)>,
+    pub comment: Option<String>,
+    pub markup: Markup,
+    // every other property, kept as read so that files are written back unchanged
+    pub properties: Vec<(String, Vec<String>)>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+impl TreeNode {
+    fn new(parent: Option<usize>) -> TreeNode {
+        TreeNode {
+            played: None,
+            comment: None,
+            markup: Markup::default(),
+            properties: vec![],
+            parent,
+            children: vec![],
+        }
+    }
+
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    // Nodes following this one, the first being the main line
+    pub fn children(&self) -> &[usize] {
+        &self.children
+    }
+}
+
+/*
+    Game record with variations. Nodes are stored in an arena and referred to by index, the root being 0.
+    A cursor on the current node is used to walk through the tree and to add moves and annotations.
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameTree {
+    nodes: Vec<TreeNode>,
+    current: usize,
+    shape: BoardSize,
+}
+
+impl GameTree {
+    pub fn new(shape: BoardSize) -> GameTree {
+        let mut root = TreeNode::new(None);
+        let size = if shape.0 == shape.1 {
+            format!("{}", shape.1)
+        } else {
+            format!("{}:{}", shape.1, shape.0)
+        };
+        root.properties = vec![
+            ("GM".to_string(), vec!["1".to_string()]),
+            ("FF".to_string(), vec!["4".to_string()]),
+            ("SZ".to_string(), vec![size]),
+        ];
+        GameTree { nodes: vec![root], current: 0, shape }
+    }
+
+    // Tree holding the game information and the moves played in the game
+    pub fn from_game(game: &Game) -> Result<GameTree, SgfError> {
+        Self::from_sgf(&write_game(game))
+    }
+
+    // First game tree of an SGF collection
+    pub fn from_sgf(text: &str) -> Result<GameTree, SgfError> {
+        let trees = parse(text)?;
+        let shape = parse_size(&trees[0].nodes[0])?;
+        let mut game_tree = GameTree { nodes: vec![], current: 0, shape };
+        game_tree.add_sgf_tree(&trees[0], None)?;
+        Ok(game_tree)
+    }
+
+    fn add_sgf_tree(&mut self, tree: &SgfTree, mut parent: Option<usize>) -> Result<(), SgfError> {
+        for sgf_node in &tree.nodes {
+            let node = self.read_node(sgf_node, parent)?;
+            let index = self.nodes.len();
+            self.nodes.push(node);
+            if let Some(parent) = parent {
+                self.nodes[parent].children.push(index);
+            }
+            parent = Some(index);
+        }
+        for variation in &tree.variations {
+            self.add_sgf_tree(variation, parent)?;
+        }
+        Ok(())
+    }
+
+    fn read_node(&self, sgf_node: &SgfNode, parent: Option<usize>) -> Result<TreeNode, SgfError> {
+        let mut node = TreeNode::new(parent);
+        let points = |property: &str, values: &[String]| {
+            parse_point_list(values).ok_or_else(|| invalid_property(property, &values.join("][")))
+        };
+        for (property, values) in &sgf_node.properties {
+            match property.as_str() {
+                "B" | "W" => {
+                    let stone = if property == "B" { BLACK_STONE } else { WHITE_STONE };
+                    let played_move = parse_move(&values[0], self.shape).ok_or_else(|| invalid_property(property, &values[0]))?;
+                    node.played = Some((stone, played_move));
+                }
+                "C" => node.comment = Some(values[0].clone()),
+                "TR" => node.markup.triangles = points(property, values)?,
+                "SQ" => node.markup.squares = points(property, values)?,
+                "CR" => node.markup.circles = points(property, values)?,
+                "LB" => {
+                    for value in values {
+                        let label = value
+                            .split_once(':')
+                            .and_then(|(coords, text)| Some((Point::from_sgf(coords)?.into(), text.to_string())))
+                            .ok_or_else(|| invalid_property(property, value))?;
+                        node.markup.labels.push(label);
+                    }
+                }
+                _ => node.properties.push((property.clone(), values.clone())),
+            }
+        }
+        Ok(node)
+    }
+
+    pub fn to_sgf(&self) -> String {
+        let mut sgf = String::new();
+        self.write_variation(&mut sgf, 0);
+        sgf.push('\n');
+        sgf
+    }
+
+    // Writes the variation starting at index, up to the end of the tree
+    fn write_variation(&self, sgf: &mut String, index: usize) {
+        sgf.push('(');
+        let mut index = index;
+        loop {
+            self.write_node(sgf, index);
+            match self.nodes[index].children.as_slice() {
+                [] => break,
+                [child] => {
+                    sgf.push('\n');
+                    index = *child;
+                }
+                children => {
+                    for child in children {
+                        sgf.push('\n');
+                        self.write_variation(sgf, *child);
+                    }
+                    break;
+                }
+            }
+        }
+        sgf.push(')');
+    }
+
+    fn write_node(&self, sgf: &mut String, index: usize) {
+        let node = &self.nodes[index];
+        sgf.push(';');
+        if let Some((stone, played_move)) = node.played {
+            let value = match played_move {
+                Move::Play(coords) => Point::from(coords).to_sgf(),
+                _ => String::new(),
+            };
+            write!(sgf, "{}[{}]", color(stone), value).unwrap();
+        }
+        for (property, values) in &node.properties {
+            sgf.push_str(property);
+            for value in values {
+                write!(sgf, "[{}]", escape_text(value)).unwrap();
+            }
+        }
+        if let Some(comment) = &node.comment {
+            write!(sgf, "C[{}]", escape_text(comment)).unwrap();
+        }
+        for (property, points) in [
+            ("TR", &node.markup.triangles),
+            ("SQ", &node.markup.squares),
+            ("CR", &node.markup.circles),
+        ] {
+            if !points.is_empty() {
+                sgf.push_str(property);
+                for coords in points {
+                    write!(sgf, "[{}]", Point::from(*coords).to_sgf()).unwrap();
+                }
+            }
+        }
+        if !node.markup.labels.is_empty() {
+            sgf.push_str("LB");
+            for (coords, text) in &node.markup.labels {
+                write!(sgf, "[{}:{}]", Point::from(*coords).to_sgf(), escape_text(text)).unwrap();
+            }
+        }
+    }
+
+    pub fn shape(&self) -> BoardSize {
+        self.shape
+    }
+
+    pub fn root(&self) -> usize {
+        0
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn node(&self, index: usize) -> &TreeNode {
+        &self.nodes[index]
+    }
+
+    pub fn current_node(&self) -> &TreeNode {
+        &self.nodes[self.current]
+    }
+
+    pub fn current_node_mut(&mut self) -> &mut TreeNode {
+        &mut self.nodes[self.current]
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // Moves to the node of the given index, false if there is no such node
+    pub fn go_to(&mut self, index: usize) -> bool {
+        if index >= self.nodes.len() {
+            return false;
+        }
+        self.current = index;
+        true
+    }
+
+    pub fn go_to_root(&mut self) {
+        self.current = 0;
+    }
+
+    // Moves to the parent of the current node, false at the root
+    pub fn go_back(&mut self) -> bool {
+        match self.nodes[self.current].parent {
+            Some(parent) => {
+                self.current = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Moves to the given variation following the current node, 0 being the main line
+    pub fn go_forward(&mut self, variation: usize) -> bool {
+        match self.nodes[self.current].children.get(variation) {
+            Some(child) => {
+                self.current = *child;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Nodes from the root to the current node
+    pub fn path(&self) -> Vec<usize> {
+        let mut path = vec![self.current];
+        while let Some(parent) = self.nodes[*path.last().unwrap()].parent {
+            path.push(parent);
+        }
+        path.reverse();
+        path
+    }
+
+    // Nodes from the root following the first variation everywhere
+    pub fn main_line(&self) -> Vec<usize> {
+        let mut line = vec![0];
+        while let Some(child) = self.nodes[*line.last().unwrap()].children.first() {
+            line.push(*child);
+        }
+        line
+    }
+
+    /*
+        Adds a move after the current node and moves to it.
+        If the move is already one of the variations there, that variation is followed instead.
+        A resignation is not a node: it becomes the result of the game in the root node and the cursor stays
+    */
+    pub fn add_move(&mut self, stone: Stone, played_move: Move) -> usize {
+        if played_move == Move::Resign {
+            let winner = if stone == BLACK_STONE { WHITE_STONE } else { BLACK_STONE };
+            let result = GameResult::Resignation(winner).to_string();
+            let root = &mut self.nodes[0];
+            root.properties.retain(|(property, _)| property != "RE");
+            root.properties.push(("RE".to_string(), vec![result]));
+            return self.current;
+        }
+        let existing = self.nodes[self.current]
+            .children
+            .iter()
+            .find(|child| self.nodes[**child].played == Some((stone, played_move)));
+        if let Some(child) = existing {
+            self.current = *child;
+            return self.current;
+        }
+        let mut node = TreeNode::new(Some(self.current));
+        node.played = Some((stone, played_move));
+        let index = self.nodes.len();
+        self.nodes.push(node);
+        self.nodes[self.current].children.push(index);
+        self.current = index;
+        index
+    }
+
+    pub fn set_comment(&mut self, comment: &str) {
+        self.nodes[self.current].comment = Some(comment.to_string());
+    }
+
+    fn is_on_board(&self, (x, y): Coordinates) -> bool {
+        x < self.shape.0 && y < self.shape.1
+    }
+
+    // Marks of the current node, each returning false and marking nothing outside the board
+    pub fn add_label(&mut self, coords: Coordinates, text: &str) -> bool {
+        if !self.is_on_board(coords) {
+            return false;
+        }
+        self.nodes[self.current].markup.labels.push((coords, text.to_string()));
+        true
+    }
+
+    pub fn add_triangle(&mut self, coords: Coordinates) -> bool {
+        if !self.is_on_board(coords) {
+            return false;
+        }
+        self.nodes[self.current].markup.triangles.push(coords);
+        true
+    }
+
+    pub fn add_square(&mut self, coords: Coordinates) -> bool {
+        if !self.is_on_board(coords) {
+            return false;
+        }
+        self.nodes[self.current].markup.squares.push(coords);
+        true
+    }
+
+    pub fn add_circle(&mut self, coords: Coordinates) -> bool {
+        if !self.is_on_board(coords) {
+            return false;
+        }
+        self.nodes[self.current].markup.circles.push(coords);
+        true
+    }
+
+    /*
+        Game after the moves and setup from the root to the current node, ready to be continued or analysed:
+        the result of the game is left out. Comments of move nodes go with their move,
+        the game has no place for the markup
+    */
+    pub fn game_at_current(&self, players: [Rc<Box<dyn Player>>; 2], display: bool) -> Result<Game, SgfError> {
+        let nodes = self
+            .path()
+            .into_iter()
+            .map(|index| {
+                let node = &self.nodes[index];
+                let mut properties = node.properties.clone();
+                properties.retain(|(property, _)| property != "RE");
+                if let Some((stone, played_move)) = node.played {
+                    let value = match played_move {
+                        Move::Play(coords) => Point::from(coords).to_sgf(),
+                        _ => String::new(),
+                    };
+                    properties.push((color(stone).to_string(), vec![value]));
+                }
+                if let Some(comment) = &node.comment {
+                    properties.push(("C".to_string(), vec![comment.clone()]));
+                }
+                SgfNode { properties }
+            })
+            .collect();
+        game_from_tree(&SgfTree { nodes, variations: vec![] }, players, display)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::players;
+
+    #[test]
+    fn cursor_moves_report_missing_nodes() {
+        let mut tree = GameTree::new((9, 9));
+        let first = tree.add_move(BLACK_STONE, Move::Play((2, 2)));
+        assert!(tree.go_back());
+        assert!(!tree.go_back());
+        assert!(!tree.go_forward(1));
+        assert!(tree.go_to(first));
+        assert!(!tree.go_to(tree.len()));
+        assert_eq!(tree.current(), first);
+    }
+
+    #[test]
+    fn resignation_becomes_the_result() {
+        let mut tree = GameTree::new((9, 9));
+        let first = tree.add_move(BLACK_STONE, Move::Play((2, 2)));
+        assert_eq!(tree.add_move(WHITE_STONE, Move::Resign), first);
+        assert_eq!(tree.len(), 2);
+        let sgf = tree.to_sgf();
+        assert!(sgf.contains("RE[B+R]"));
+        assert!(!sgf.contains("W[]"));
+    }
+
+    #[test]
+    fn markup_outside_the_board_is_refused() {
+        let mut tree = GameTree::new((9, 13));
+        assert!(tree.add_triangle((8, 12)));
+        assert!(!tree.add_triangle((9, 0)));
+        assert!(!tree.add_square((0, 13)));
+        assert!(!tree.add_circle((20, 20)));
+        assert!(!tree.add_label((9, 9), "A"));
+        assert_eq!(tree.current_node().markup.triangles, vec![(8, 12)]);
+        assert!(tree.current_node().markup.squares.is_empty() && tree.current_node().markup.labels.is_empty());
+    }
+
+    #[test]
+    fn game_at_current_keeps_move_comments() {
+        let mut tree = GameTree::new((9, 9));
+        tree.add_move(BLACK_STONE, Move::Play((2, 2)));
+        tree.set_comment("good shape");
+        tree.add_move(WHITE_STONE, Move::Play((6, 6)));
+        tree.go_back();
+        let game = tree.game_at_current(players(), false).unwrap();
+        assert_eq!(game.move_number(), 1);
+        assert_eq!(game.last_record().unwrap().comment.as_deref(), Some("good shape"));
+
+        let from_game = GameTree::from_game(&game).unwrap();
+        assert_eq!(from_game.len(), 2);
+        assert_eq!(from_game.node(1).comment.as_deref(), Some("good shape"));
+    }
+
+    #[test]
+    fn tree_with_variations_and_markup_reads_back_the_same() {
+        let text = "(;GM[1]FF[4]SZ[9]KM[7.5]PB[Black]C[root \\] comment]\
+            ;B[cc]C[first move]TR[dd][ee]LB[cd:A][dc:note]\
+            (;W[gg]SQ[aa:ab]CR[ff];B[])\
+            (;W[gc]C[a variation]LB[gg:1]\
+            (;B[gg])(;B[cg]TR[cc])))";
+        let tree = GameTree::from_sgf(text).unwrap();
+        assert_eq!(tree.len(), 7);
+        assert_eq!(tree.node(1).children().len(), 2);
+        assert_eq!(tree.node(0).comment.as_deref(), Some("root ] comment"));
+        assert_eq!(tree.node(1).markup.triangles, vec![(3, 3), (4, 4)]);
+        assert_eq!(tree.node(1).markup.labels, vec![((3, 2), "A".to_string()), ((2, 3), "note".to_string())]);
+        assert_eq!(tree.node(2).markup.squares, vec![(0, 0), (1, 0)]);
+        assert_eq!(tree.node(3).played, Some((BLACK_STONE, Move::Pass)));
+
+        let written = tree.to_sgf();
+        let read = GameTree::from_sgf(&written).unwrap();
+        assert_eq!(read, tree);
+        assert_eq!(read.to_sgf(), written);
+    }
+}
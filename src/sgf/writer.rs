@@ -0,0 +1,207 @@
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use crate::game::{Coordinates, Game, Move, MoveRecord, SetupRecord};
+use crate::point::Point;
+use crate::sgf::{color, escape_text, rules_property, win_rate_comment};
+use crate::stones::{BLACK_STONE, EMPTY, WHITE_STONE};
+
+const APPLICATION: &str = concat!("gobot-rs:", env!("CARGO_PKG_VERSION"));
+
+fn write_points(sgf: &mut String, property: &str, points: &[Coordinates]) {
+    if points.is_empty() {
+        return;
+    }
+    sgf.push_str(property);
+    for coords in points {
+        write!(sgf, "[{}]", Point::from(*coords).to_sgf()).unwrap();
+    }
+}
+
+fn root_properties(game: &Game) -> String {
+    let mut sgf = String::new();
+    let (height, width) = game.shape();
+    write!(sgf, "GM[1]FF[4]CA[UTF-8]AP[{}]", APPLICATION).unwrap();
+    if height == width {
+        write!(sgf, "SZ[{}]", width).unwrap();
+    } else {
+        write!(sgf, "SZ[{}:{}]", width, height).unwrap();
+    }
+    write!(sgf, "KM[{}]RU[{}]", game.komi(), escape_text(&rules_property(game.rules()))).unwrap();
+    if game.handicap() > 0 {
+        write!(sgf, "HA[{}]", game.handicap()).unwrap();
+    }
+    for stone in [BLACK_STONE, WHITE_STONE] {
+        let name = game.find_player(stone).get_name().to_string();
+        write!(sgf, "P{}[{}]", color(stone), escape_text(&name)).unwrap();
+    }
+    if let Some(result) = game.result() {
+        write!(sgf, "RE[{}]", result).unwrap();
+    }
+
+    // stones of the start position, handicap included
+    let mut setup: [Vec<Coordinates>; 2] = [vec![], vec![]];
+    for (x, row) in game.start_board().rows().iter().enumerate() {
+        for (y, stone) in row.iter().enumerate() {
+            if *stone == BLACK_STONE {
+                setup[0].push((x, y));
+            } else if *stone == WHITE_STONE {
+                setup[1].push((x, y));
+            }
+        }
+    }
+    write_points(&mut sgf, "AB", &setup[0]);
+    write_points(&mut sgf, "AW", &setup[1]);
+    if game.start_player() != BLACK_STONE {
+        write!(sgf, "PL[{}]", color(game.start_player())).unwrap();
+    }
+    sgf
+}
+
+// Node of a setup made between two moves
+fn setup_node(setup: &SetupRecord) -> String {
+    let mut sgf = String::from("\n;");
+    for (property, stone) in [("AB", BLACK_STONE), ("AW", WHITE_STONE), ("AE", EMPTY)] {
+        let points: Vec<Coordinates> = setup
+            .stones
+            .iter()
+            .filter(|(_, point_stone)| *point_stone == stone)
+            .map(|(coords, _)| *coords)
+            .collect();
+        write_points(&mut sgf, property, &points);
+    }
+    write!(sgf, "PL[{}]", color(setup.to_play)).unwrap();
+    sgf
+}
+
+// Comment and evaluation of a move, the evaluation being also written as SBKV for Sabaki
+fn annotations(record: &MoveRecord) -> String {
+    let mut sgf = String::new();
+    let mut comment: Vec<String> = vec![];
+    if let Some(text) = &record.comment {
+        comment.push(text.clone());
+    }
+    if let Some(evaluation) = record.evaluation {
+        comment.push(win_rate_comment(record.color, evaluation));
+        // Sabaki reads win rates from Black's point of view
+        let black_win_rate = if record.color == BLACK_STONE { evaluation } else { 1.0 - evaluation };
+        write!(sgf, "SBKV[{:.1}]", 100.0 * black_win_rate).unwrap();
+    }
+    if !comment.is_empty() {
+        write!(sgf, "C[{}]", escape_text(&comment.join("\n"))).unwrap();
+    }
+    sgf
+}
+
+/*
+    Main line of the game in SGF FF[4]: game information in the root node, then one node per move.
+    Passes are empty moves, a resignation is a node without move that holds its comment and evaluation.
+    Setups made during the game get their own node
+*/
+pub fn write_game(game: &Game) -> String {
+    let mut sgf = format!("(;{}", root_properties(game));
+    for (i, record) in game.records().iter().enumerate() {
+        for setup in game.setups().iter().filter(|setup| setup.move_number == i) {
+            sgf.push_str(&setup_node(setup));
+        }
+        match record.played_move {
+            Move::Play(coords) => write!(sgf, "\n;{}[{}]", color(record.color), Point::from(coords).to_sgf()).unwrap(),
+            Move::Pass => write!(sgf, "\n;{}[]", color(record.color)).unwrap(),
+            Move::Resign => sgf.push_str("\n;"),
+        }
+        sgf.push_str(&annotations(record));
+    }
+    for setup in game.setups().iter().filter(|setup| setup.move_number == game.move_number()) {
+        sgf.push_str(&setup_node(setup));
+    }
+    sgf.push_str(")\n");
+    sgf
+}
+
+pub fn save_game(game: &Game, path: &str) -> io::Result<()> {
+    fs::write(path, write_game(game))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameResult;
+    use crate::rules::{KoRule, Rules};
+    use crate::sgf::reader::read_game;
+    use crate::signals::Signals;
+    use crate::test_support::players;
+
+    #[test]
+    fn written_game_reads_back_the_same() {
+        let rules = Rules { komi: 0.5, ..Rules::japanese() };
+        let mut game = Game::new(players(), (9, 9), false, rules).unwrap();
+        game.set_fixed_handicap(3).unwrap();
+        for played_move in [Move::Play((4, 4)), Move::Play((4, 5)), Move::Pass, Move::Play((0, 0))] {
+            assert_eq!(game.step(played_move), Ok(()));
+        }
+        game.set_last_comment("a comment with ] and \\");
+        assert_eq!(game.step(Move::Resign), Err(Signals::Resignation));
+        assert_eq!(game.result(), Some(GameResult::Resignation(BLACK_STONE)));
+
+        let read = read_game(&write_game(&game), players(), false).unwrap();
+        assert_eq!(read.moves().collect::<Vec<Move>>(), game.moves().collect::<Vec<Move>>());
+        assert_eq!(read.handicap(), 3);
+        assert_eq!(read.start_board().rows(), game.start_board().rows());
+        assert_eq!(read.komi(), 0.5);
+        assert_eq!(read.rules().name, Rules::japanese().name);
+        assert_eq!(read.result(), game.result());
+        assert_eq!(read.position_hash(), game.position_hash());
+        assert_eq!(read.records()[3].comment.as_deref(), Some("a comment with ] and \\"));
+    }
+
+    #[test]
+    fn scored_result_and_comments_read_back() {
+        let mut game = Game::new(players(), (5, 5), false, Rules::default()).unwrap();
+        game.step(Move::Play((2, 2))).unwrap();
+        game.set_last_comment("first line\nsecond line");
+        game.set_last_evaluation(0.75);
+        game.set_result(GameResult::Score(WHITE_STONE, 2.5));
+
+        let read = read_game(&write_game(&game), players(), false).unwrap();
+        let record = read.last_record().unwrap();
+        assert_eq!(record.comment.as_deref(), Some("first line\nsecond line"));
+        assert!((record.evaluation.unwrap() - 0.75).abs() < 1e-3);
+        assert_eq!(read.result(), Some(GameResult::Score(WHITE_STONE, 2.5)));
+    }
+
+    #[test]
+    fn resignation_keeps_its_comment_and_evaluation() {
+        let mut game = Game::new(players(), (9, 9), false, Rules::default()).unwrap();
+        game.step(Move::Play((4, 4))).unwrap();
+        assert_eq!(game.step(Move::Resign), Err(Signals::Resignation));
+        game.set_last_comment("no way to live");
+        game.set_last_evaluation(0.05);
+
+        let sgf = write_game(&game);
+        assert!(sgf.ends_with("\n;SBKV[95.0]C[no way to live\nW win rate: 5.0%])\n"));
+        let read = read_game(&sgf, players(), false).unwrap();
+        assert_eq!(read.moves().collect::<Vec<Move>>(), vec![Move::Play((4, 4)), Move::Resign]);
+        assert_eq!(read.result(), Some(GameResult::Resignation(BLACK_STONE)));
+        let record = read.last_record().unwrap();
+        assert_eq!(record.color, WHITE_STONE);
+        assert_eq!(record.comment.as_deref(), Some("no way to live"));
+        assert!((record.evaluation.unwrap() - 0.05).abs() < 1e-3);
+    }
+
+    #[test]
+    fn customized_rules_read_back_the_same() {
+        let rules = Rules {
+            ko_rule: KoRule::SituationalSuperko,
+            suicide_allowed: true,
+            komi: 5.5,
+            ..Rules::japanese()
+        };
+        let game = Game::new(players(), (9, 9), false, rules).unwrap();
+        let sgf = write_game(&game);
+        assert!(sgf.contains("KM[5.5]RU[Japanese:ko=situational,suicide=yes]"));
+        assert_eq!(*read_game(&sgf, players(), false).unwrap().rules(), rules);
+
+        let game = Game::new(players(), (9, 9), false, Rules::aga()).unwrap();
+        assert!(write_game(&game).contains("RU[AGA]"));
+    }
+}
@@ -1,13 +1,19 @@
 use std::fmt;
+use crate::board::{MAX_SIDE, MIN_SIDE};
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 pub enum Signals {
     InducesSuicide,
     OccupiedCase,
     BreakingKo,
+    BreakingSuperko,
+    InvalidHandicap,
+    InvalidBoardSize,
     OutsideBounds,
+    InvalidSetup,
     GameOver,
     DoublePass,
+    Resignation,
 }
 
 impl fmt::Display for Signals {
@@ -16,9 +22,14 @@ impl fmt::Display for Signals {
             Signals::InducesSuicide => write!(f, "Move induces suicide with no capture"),
             Signals::OccupiedCase => write!(f, "Move on an occupied case"),
             Signals::BreakingKo => write!(f, "Ko rule is not respected"),
+            Signals::BreakingSuperko => write!(f, "Move repeats an earlier position"),
+            Signals::InvalidHandicap => write!(f, "Handicap stones cannot be placed"),
+            Signals::InvalidBoardSize => write!(f, "Board sides must be between {} and {}", MIN_SIDE, MAX_SIDE),
             Signals::OutsideBounds => write!(f, "Tried to place a stone outside of board"),
+            Signals::InvalidSetup => write!(f, "Setup stones leave a chain without liberties"),
             Signals::GameOver => write!(f, "Game is over"),
             Signals::DoublePass => write!(f, "Both players have passed"),
+            Signals::Resignation => write!(f, "Player resigned"),
         }
     }
 }
@@ -0,0 +1,29 @@
+// Helpers shared by the unit tests of the crate
+use std::rc::Rc;
+use crate::game::{Coordinates, Game};
+use crate::players::human::Human;
+use crate::players::player::Player;
+use crate::rules::Rules;
+use crate::stones::{Stone, BLACK_STONE, WHITE_STONE};
+
+// Players of games that are only driven by the tests, they are never asked for a move
+pub fn players() -> [Rc<Box<dyn Player>>; 2] {
+    [Rc::new(Box::new(Human::new(BLACK_STONE))), Rc::new(Box::new(Human::new(WHITE_STONE)))]
+}
+
+// Position drawn row by row from the top, X for Black and O for White, with Black to play
+pub fn position(rows: &[&str], rules: Rules) -> Game {
+    let mut game = Game::new(players(), (rows.len(), rows[0].len()), false, rules).unwrap();
+    let mut stones: Vec<(Coordinates, Stone)> = vec![];
+    for (x, row) in rows.iter().enumerate() {
+        for (y, point) in row.chars().enumerate() {
+            match point {
+                'X' => stones.push(((x, y), BLACK_STONE)),
+                'O' => stones.push(((x, y), WHITE_STONE)),
+                _ => {}
+            }
+        }
+    }
+    game.set_stones(&stones, BLACK_STONE).unwrap();
+    game
+}
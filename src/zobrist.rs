@@ -0,0 +1,48 @@
+use crate::board::MAX_SIDE;
+use crate::stones::Stone;
+
+const SEED: u64 = 0x2545_F491_4F6C_DD1D;
+const POINTS: usize = MAX_SIDE * MAX_SIDE;
+
+// Keys are generated at compile time so that hashes stay stable between runs
+static STONE_KEYS: [[u64; 2]; POINTS] = generate_stone_keys();
+const WHITE_TO_MOVE_KEY: u64 = splitmix64(SEED).1;
+
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_stone_keys() -> [[u64; 2]; POINTS] {
+    let mut keys = [[0; 2]; POINTS];
+    let mut state = splitmix64(SEED).0;
+    let mut i = 0;
+    while i < POINTS {
+        let mut color = 0;
+        while color < 2 {
+            let (next_state, key) = splitmix64(state);
+            state = next_state;
+            keys[i][color] = key;
+            color += 1;
+        }
+        i += 1;
+    }
+    keys
+}
+
+pub fn stone_key((x, y): (usize, usize), stone: Stone) -> u64 {
+    match stone {
+        Some(color) => STONE_KEYS[x * MAX_SIDE + y][color as usize],
+        None => 0,
+    }
+}
+
+pub fn side_to_move_key(stone: Stone) -> u64 {
+    match stone {
+        Some(true) => WHITE_TO_MOVE_KEY,
+        _ => 0,
+    }
+}